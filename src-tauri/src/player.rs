@@ -1,12 +1,27 @@
 mod player {
+    use crate::sound_theme;
+    use crate::ui_handler::{AudioSettings, Phase, Sound, Tone};
     use crate::utils::*;
-    //use log::debug;
-    use rodio::{source::Source, Decoder, OutputStream};
-    use std::io::Cursor;
+    use log::debug;
+    use rodio::buffer::SamplesBuffer;
+    use rodio::cpal::traits::HostTrait;
+    use rodio::source::{Source, Zero};
+    use rodio::{Decoder, DeviceTrait, OutputStream, OutputStreamHandle, Sink, StreamError};
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+    use std::process::Command;
     use std::thread;
+    use std::time::Duration;
 
     const BEEP_INTERVAL: u64 = 1000; //milliseconds
     const PLAY_DURATION: u64 = 1000;
+    //volume of soft alerts, which shouldn't startle
+    const QUIET: f32 = 0.3;
+    //synthesised tones start and end this softly to avoid clicks
+    const FADE: u64 = 10; //milliseconds
+    const SAMPLE_RATE: u32 = 48000;
+    //theme sounds are cut off after this, any file may be picked
+    const MAX_THEME_SOUND: u64 = 10; //seconds
 
     #[cfg(feature = "debug")]
     const MAX_TIMES: u64 = 1;
@@ -14,29 +29,252 @@ mod player {
     #[cfg(not(feature = "debug"))]
     const MAX_TIMES: u64 = 5;
 
-    pub fn play() {
+    /// Plays the alert of a reminder category
+    pub fn play_sound(sound: &Sound, audio: &AudioSettings) {
+        match sound {
+            Sound::Beeps => beep(audio, MAX_TIMES, BEEP_INTERVAL, 1.0, 1.0),
+            Sound::Beep => beep(audio, 1, BEEP_INTERVAL, 1.0, 1.0),
+            Sound::Soft => beep(audio, 1, BEEP_INTERVAL, QUIET, 1.0),
+            Sound::Double => beep(audio, 2, BEEP_INTERVAL / 2, 1.0, 1.0),
+            Sound::Triple => beep(audio, 3, BEEP_INTERVAL * 2, 1.0, 1.0),
+            Sound::Tones(tones) => play_tones(audio, tones),
+            Sound::Speech(message) => speak(audio, message),
+            Sound::Theme(name) => play_theme_sound(audio, name),
+        }
+    }
+
+    /// Two quick, higher pitched beeps for the end of a micro-break
+    pub fn play_chime(audio: &AudioSettings) {
+        beep(audio, 2, BEEP_INTERVAL / 4, QUIET, 1.5);
+    }
+
+    /// Alert for the start of a Pomodoro phase. A single beep sends you back
+    /// to work, quick double beeps start a short break and slow triple beeps
+    /// a long one
+    pub fn play_phase(phase: &Phase, audio: &AudioSettings) {
+        match phase {
+            Phase::Work => play_sound(&Sound::Beep, audio),
+            Phase::ShortBreak => play_sound(&Sound::Double, audio),
+            Phase::LongBreak => play_sound(&Sound::Triple, audio),
+        }
+    }
+
+    /// Long run of quick beeps for reaching the daily limit
+    pub fn play_limit(audio: &AudioSettings) {
+        beep(audio, MAX_TIMES * 3, BEEP_INTERVAL / 2, 1.0, 1.0);
+    }
+
+    /// Repeats an unanswered alert, with more beeps closer together at each
+    /// level. They start out soft and reach the set volume at `max_level`,
+    /// so they never clip
+    pub fn play_escalation(level: usize, max_level: usize, audio: &AudioSettings) {
+        let volume = QUIET + (1.0 - QUIET) * level.min(max_level) as f32 / max_level.max(1) as f32;
+        let level = level as u64;
+        beep(
+            audio,
+            1 + level,
+            BEEP_INTERVAL / (1 + level.min(3)),
+            volume,
+            1.0,
+        );
+    }
+
+    /// Plays sine tones in turn. Each fades in and out over FADE to avoid
+    /// clicks at either end
+    fn play_tones(audio: &AudioSettings, tones: &[Tone]) {
+        if let LockedState::Locked = is_locked() {
+            return;
+        }
+
+        let (_stream, sink) = match open_sink(audio) {
+            Some(sink) => sink,
+            None => return,
+        };
+
+        for tone in tones {
+            sink.append(SamplesBuffer::new(1, SAMPLE_RATE, tone_samples(tone)));
+
+            if tone.gap > 0 {
+                let gap =
+                    Zero::<f32>::new(1, SAMPLE_RATE).take_duration(Duration::from_millis(tone.gap));
+                sink.append(gap);
+            }
+        }
+
+        sink.sleep_until_end();
+    }
+
+    /// Samples of a sine tone, faded in and out over FADE or a half of the
+    /// tone if it is shorter
+    fn tone_samples(tone: &Tone) -> Vec<f32> {
+        let len = (tone.duration * SAMPLE_RATE as u64 / 1000) as usize;
+        let fade = (FADE * SAMPLE_RATE as u64 / 1000)
+            .min(len as u64 / 2)
+            .max(1) as f32;
+        let step = 2.0 * std::f32::consts::PI * tone.frequency as f32 / SAMPLE_RATE as f32;
+
+        (0..len)
+            .map(|i| {
+                let edge = i.min(len - 1 - i) as f32;
+                (step * i as f32).sin() * (edge / fade).min(1.0)
+            })
+            .collect()
+    }
+
+    /// Speaks the message with espeak-ng, or speech-dispatcher if that is
+    /// missing. Beeps once if neither can speak. Both use the default output
+    /// device. Returns at once, the speech runs on its own thread
+    fn speak(audio: &AudioSettings, message: &str) {
+        if let LockedState::Locked = is_locked() {
+            return;
+        }
+
+        let (audio, message) = (audio.clone(), message.to_string());
+        thread::spawn(move || speak_blocking(&audio, &message));
+    }
+
+    fn speak_blocking(audio: &AudioSettings, message: &str) {
+        let volume = audio.volume.min(100);
+        let engines = [
+            ("espeak-ng", vec!["-a".to_string(), volume.to_string()]),
+            //waits for the speech to end; volume from -100 to 100
+            (
+                "spd-say",
+                vec![
+                    "-w".to_string(),
+                    "-i".to_string(),
+                    (volume as i64 * 2 - 100).to_string(),
+                ],
+            ),
+        ];
+
+        for (engine, args) in engines {
+            match Command::new(engine).args(&args).arg(message).status() {
+                Ok(status) if status.success() => return,
+                Ok(status) => debug!("player: {} failed: {}", engine, status),
+                Err(e) => debug!("player: unable to run {}: {}", engine, e),
+            }
+        }
+
+        beep(audio, 1, BEEP_INTERVAL, 1.0, 1.0);
+    }
+
+    /// Plays a sound of the desktop's sound theme for up to MAX_THEME_SOUND
+    /// seconds. Beeps once if the theme has no such sound or it can't be
+    /// decoded
+    fn play_theme_sound(audio: &AudioSettings, name: &str) {
+        if let LockedState::Locked = is_locked() {
+            return;
+        }
+
+        let path = match sound_theme::lookup(name) {
+            Some(path) => path,
+            None => {
+                debug!("player: no theme sound {}", name);
+                return beep(audio, 1, BEEP_INTERVAL, 1.0, 1.0);
+            }
+        };
+
+        let source = File::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| Decoder::new(BufReader::new(file)).map_err(|e| e.to_string()));
+        let source = match source {
+            Ok(source) => source,
+            Err(e) => {
+                debug!("player: unable to play {}: {}", path.display(), e);
+                return beep(audio, 1, BEEP_INTERVAL, 1.0, 1.0);
+            }
+        };
+
+        let (_stream, sink) = match open_sink(audio) {
+            Some(sink) => sink,
+            None => return,
+        };
+        sink.append(source.take_duration(Duration::from_secs(MAX_THEME_SOUND)));
+        sink.sleep_until_end();
+    }
+
+    /// Names of the audio output devices
+    pub fn output_devices() -> Vec<String> {
+        match rodio::cpal::default_host().output_devices() {
+            Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+            Err(e) => {
+                debug!("player: unable to list output devices: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Stream to the chosen output device, or the default one if it is
+    /// missing
+    fn open_stream(
+        audio: &AudioSettings,
+    ) -> Result<(OutputStream, OutputStreamHandle), StreamError> {
+        if let Some(name) = &audio.device {
+            let device = rodio::cpal::default_host()
+                .output_devices()
+                .ok()
+                .and_then(|mut devices| devices.find(|d| d.name().ok().as_ref() == Some(name)));
+
+            match device.map(|d| OutputStream::try_from_device(&d)) {
+                Some(Ok(stream)) => return Ok(stream),
+                Some(Err(e)) => debug!("player: unable to open {}: {}", name, e),
+                None => debug!("player: output device {} not found", name),
+            }
+        }
+
+        OutputStream::try_default()
+    }
+
+    /// Sink at the set volume. The stream has to be kept for as long as the
+    /// sink plays. None if there is no output device to play on
+    fn open_sink(audio: &AudioSettings) -> Option<(OutputStream, Sink)> {
+        let (stream, stream_handle) = open_stream(audio)
+            .map_err(|e| debug!("player: unable to open output stream: {}", e))
+            .ok()?;
+        let sink = Sink::try_new(&stream_handle)
+            .map_err(|e| debug!("player: unable to create sink: {}", e))
+            .ok()?;
+        sink.set_volume(audio.volume.min(100) as f32 / 100.0);
+
+        Some((stream, sink))
+    }
+
+    /// Plays the beep `times` times, `interval` milliseconds apart. `speed`
+    /// above 1 raises the pitch and shortens the beep. `volume` is scaled by
+    /// the volume of the preferences
+    fn beep(audio: &AudioSettings, times: u64, interval: u64, volume: f32, speed: f32) {
         match is_locked() {
             LockedState::Locked => return,
             _ => (),
         }
 
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        let (_stream, stream_handle) = match open_stream(audio) {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!("player: unable to open output stream: {}", e);
+                return;
+            }
+        };
         let mp3_data = include_bytes!("beep.mp3");
         let cursor = Cursor::new(mp3_data);
 
         let source = Decoder::new(cursor).unwrap();
-        let buffered = source.buffered();
+        let volume = volume * audio.volume.min(100) as f32 / 100.0;
+        let buffered = source.amplify(volume).speed(speed).buffered();
 
-        for _ in 0..MAX_TIMES {
+        for _ in 0..times {
             let sh = stream_handle.clone();
             let src = buffered.clone();
 
             thread::spawn(move || {
-                sh.play_raw(src.convert_samples()).unwrap();
+                if let Err(e) = sh.play_raw(src.convert_samples()) {
+                    debug!("player: unable to play beep: {}", e);
+                }
                 std::thread::sleep(std::time::Duration::from_millis(PLAY_DURATION));
             });
 
-            std::thread::sleep(std::time::Duration::from_millis(BEEP_INTERVAL));
+            std::thread::sleep(std::time::Duration::from_millis(interval));
         }
     }
 }
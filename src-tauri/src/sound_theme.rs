@@ -0,0 +1,227 @@
+//! Finds the sounds of the desktop's sound theme, following the freedesktop
+//! sound theme spec. Themes live under sounds/ in the XDG data dirs and may
+//! inherit from others. A name not found falls back to less specific ones,
+//! e.g. alarm-clock-elapsed to alarm-clock and then alarm
+mod sound_theme {
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    //every theme falls back to this one
+    const FALLBACK_THEME: &str = "freedesktop";
+    const EXTENSIONS: [&str; 3] = ["oga", "ogg", "wav"];
+    //deeper inheritance is most likely a loop
+    const MAX_DEPTH: usize = 5;
+
+    struct Theme {
+        name: String,
+        //subdirectories holding the sounds
+        directories: Vec<String>,
+        inherits: Vec<String>,
+    }
+
+    /// File of the named sound in the desktop's theme, if there is one
+    pub fn lookup(name: &str) -> Option<PathBuf> {
+        find_sound(name, &current_theme(), &data_dirs())
+    }
+
+    /// File of the named sound in the theme or those it inherits from.
+    /// `data_dirs` are searched in turn
+    pub fn find_sound(name: &str, theme: &str, data_dirs: &[PathBuf]) -> Option<PathBuf> {
+        let themes = theme_chain(theme, data_dirs);
+        let names = fallback_names(name);
+
+        for name in &names {
+            for theme in &themes {
+                for dir in data_dirs {
+                    for sub in &theme.directories {
+                        let path = dir.join("sounds").join(&theme.name).join(sub);
+                        if let Some(file) = sound_file(&path, name) {
+                            return Some(file);
+                        }
+                    }
+                }
+            }
+        }
+
+        //sounds outside any theme come last
+        names.iter().find_map(|name| {
+            data_dirs
+                .iter()
+                .find_map(|dir| sound_file(&dir.join("sounds"), name))
+        })
+    }
+
+    /// Theme chosen in the desktop's settings. Only GNOME's is read
+    fn current_theme() -> String {
+        Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.sound", "theme-name"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .trim_matches('\'')
+                    .to_string()
+            })
+            .filter(|theme| !theme.is_empty())
+            .unwrap_or_else(|| FALLBACK_THEME.to_string())
+    }
+
+    /// XDG_DATA_HOME followed by XDG_DATA_DIRS
+    fn data_dirs() -> Vec<PathBuf> {
+        let dirs = std::env::var_os("XDG_DATA_DIRS")
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+        dirs::data_dir()
+            .into_iter()
+            .chain(std::env::split_paths(&dirs))
+            .collect()
+    }
+
+    /// The theme, those it inherits from and finally the fallback theme
+    fn theme_chain(theme: &str, data_dirs: &[PathBuf]) -> Vec<Theme> {
+        let mut chain: Vec<Theme> = Vec::new();
+        let mut pending = vec![(theme.to_string(), 0)];
+
+        while let Some((name, depth)) = pending.pop() {
+            if depth > MAX_DEPTH || chain.iter().any(|t| t.name == name) {
+                continue;
+            }
+
+            let theme = read_theme(&name, data_dirs);
+            //parents in the order listed
+            for parent in theme.inherits.iter().rev() {
+                pending.push((parent.clone(), depth + 1));
+            }
+            chain.push(theme);
+        }
+
+        if !chain.iter().any(|t| t.name == FALLBACK_THEME) {
+            chain.push(read_theme(FALLBACK_THEME, data_dirs));
+        }
+
+        chain
+    }
+
+    /// Reads index.theme of the theme. Without one the sounds are looked
+    /// for in stereo/
+    fn read_theme(name: &str, data_dirs: &[PathBuf]) -> Theme {
+        let index = data_dirs.iter().find_map(|dir| {
+            std::fs::read_to_string(dir.join("sounds").join(name).join("index.theme")).ok()
+        });
+
+        let mut theme = Theme {
+            name: name.to_string(),
+            directories: Vec::new(),
+            inherits: Vec::new(),
+        };
+
+        for line in index.iter().flat_map(|index| index.lines()) {
+            //keys of the directories' own groups are not needed
+            if line.starts_with('[') && line.trim() != "[Sound Theme]" {
+                break;
+            }
+
+            let list = |value: &str| -> Vec<String> {
+                value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect()
+            };
+            match line.split_once('=') {
+                Some((key, value)) if key.trim() == "Directories" => {
+                    theme.directories = list(value)
+                }
+                Some((key, value)) if key.trim() == "Inherits" => theme.inherits = list(value),
+                _ => (),
+            }
+        }
+
+        if theme.directories.is_empty() {
+            theme.directories.push("stereo".to_string());
+        }
+
+        theme
+    }
+
+    /// The name and those less specific, e.g. alarm-clock-elapsed,
+    /// alarm-clock, alarm
+    fn fallback_names(name: &str) -> Vec<String> {
+        let mut names = vec![name.to_string()];
+        let mut rest = name;
+        while let Some((less, _)) = rest.rsplit_once('-') {
+            names.push(less.to_string());
+            rest = less;
+        }
+
+        names
+    }
+
+    fn sound_file(dir: &Path, name: &str) -> Option<PathBuf> {
+        EXTENSIONS
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", name, ext)))
+            .find(|path| path.is_file())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn touch(path: PathBuf) {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        #[test]
+        fn test_find_sound() {
+            let root = std::env::temp_dir().join(format!("sound_theme_{}", std::process::id()));
+            let home = root.join("home");
+            let system = root.join("system");
+            let dirs = vec![home.clone(), system.clone()];
+
+            touch(home.join("sounds/ocean/index.theme"));
+            std::fs::write(
+                home.join("sounds/ocean/index.theme"),
+                "[Sound Theme]\nName=Ocean\nInherits=freedesktop\nDirectories=stereo,5.1\n\n[5.1]\nOutputProfile=5.1\n",
+            )
+            .unwrap();
+            touch(home.join("sounds/ocean/5.1/complete.oga"));
+            touch(system.join("sounds/freedesktop/stereo/complete.oga"));
+            touch(system.join("sounds/freedesktop/stereo/bell.oga"));
+            touch(system.join("sounds/freedesktop/stereo/alarm-clock.wav"));
+            touch(system.join("sounds/message.ogg"));
+
+            //the theme's own sound wins over the one inherited
+            assert_eq!(
+                find_sound("complete", "ocean", &dirs),
+                Some(home.join("sounds/ocean/5.1/complete.oga"))
+            );
+            assert_eq!(
+                find_sound("bell", "ocean", &dirs),
+                Some(system.join("sounds/freedesktop/stereo/bell.oga"))
+            );
+            assert_eq!(
+                find_sound("alarm-clock-elapsed", "ocean", &dirs),
+                Some(system.join("sounds/freedesktop/stereo/alarm-clock.wav"))
+            );
+            //unknown themes still fall back to freedesktop
+            assert_eq!(
+                find_sound("bell", "missing", &dirs),
+                Some(system.join("sounds/freedesktop/stereo/bell.oga"))
+            );
+            assert_eq!(
+                find_sound("message-new-instant", "ocean", &dirs),
+                Some(system.join("sounds/message.ogg"))
+            );
+            assert_eq!(find_sound("dialog-warning", "ocean", &dirs), None);
+
+            std::fs::remove_dir_all(root).unwrap();
+        }
+    }
+}
+
+pub use sound_theme::*;
@@ -4,16 +4,19 @@ mod alarm_manager;
 mod ui_handler {
 
     use super::alarm_manager::*;
+    use crate::player::output_devices;
     use crate::utils::*;
-    use chrono::Weekday;
+    use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
     use tauri::Emitter;
     // use log::debug;
+    use serde::de::DeserializeOwned;
     use serde::ser::SerializeStruct;
     use serde::Deserialize;
     use serde::Serialize;
     use serde::Serializer;
     use serde_json::json;
     use serde_json::Value;
+    use std::collections::{BTreeMap, HashMap};
     use std::fmt;
     use std::fs::{File, OpenOptions};
     use std::io::{Read, Write};
@@ -30,20 +33,487 @@ mod ui_handler {
     pub type BcastSender<T> = S<T>;
 
     const BCAST_CHANNEL_SIZE: usize = 10;
+    //limits of synthesised tones, past which they are inaudible or
+    //would hold up the alarm
+    const MIN_FREQUENCY: u32 = 20;
+    const MAX_FREQUENCY: u32 = 20_000;
+    const MAX_TONE_MS: u64 = 10_000;
+    //of a whole pattern of tones and gaps
+    const MAX_PATTERN_MS: u64 = 30_000;
 
-    #[derive(Debug, Serialize, Deserialize, Clone)]
+    /// How the alarms of a rule are computed
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum RuleKind {
+        /// Every `interval` minutes between `from` and `to`
+        #[default]
+        Interval,
+        /// A five field cron expression e.g. `25,55 * * * *`
+        Cron,
+        /// An RFC 5545 RRULE, optionally preceded by a DTSTART line
+        Rrule,
+    }
+
+    /// Where the alarms of an interval rule start and end. By default the
+    /// first alarm is an interval after from:00 and the last one may be at to:00
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct Anchor {
+        /// Also play an alarm at the start of the window
+        pub include_start: bool,
+        /// Play an alarm at the end of the window when one falls on it
+        pub include_end: bool,
+        /// Minutes past the hour at which the window starts and ends e.g. 5
+        /// for breaks at :05 past
+        pub offset: usize,
+    }
+
+    impl Default for Anchor {
+        fn default() -> Self {
+            Self {
+                include_start: false,
+                include_end: true,
+                offset: 0,
+            }
+        }
+    }
+
+    /// Randomises the alarms of an interval rule so that they can't be
+    /// anticipated. Seeded per day, so a day's alarms survive restarts
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case", tag = "mode")]
+    pub enum Random {
+        /// Moves each alarm by up to `minutes` either way
+        Jitter { minutes: usize },
+        /// `count` alarms anywhere in the window, at least `min_gap` minutes apart
+        Breaks {
+            count: usize,
+            #[serde(rename = "min-gap")]
+            min_gap: usize,
+        },
+    }
+
+    /// An interval which applies from a time of day onwards, on the given
+    /// days (all days of the rule when empty)
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct RampStep {
+        pub from: NaiveTime,
+        pub interval: usize,
+        #[serde(default)]
+        pub days: Vec<String>,
+    }
+
+    /// Changes the interval of an interval rule as the day goes on
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct Ramp {
+        /// Intervals replacing the rule's interval from a time of day e.g.
+        /// 40 minutes from 15:00
+        pub steps: Vec<RampStep>,
+        /// Minutes taken off the interval for every break skipped earlier
+        /// in the day, either with the skip command or by leaving its alert
+        /// unanswered until the next break
+        pub per_skip: usize,
+        /// Skipped breaks don't shorten the interval below this
+        pub min_interval: usize,
+    }
+
+    /// A daily span of time from `from` (inclusive) to `to` (exclusive).
+    /// Wraps past midnight when `to` is before `from` e.g. 22:00 to 07:00
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct TimeWindow {
+        pub from: NaiveTime,
+        pub to: NaiveTime,
+    }
+
+    /// How often the days of a rule recur
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[serde(rename_all = "kebab-case", tag = "every")]
+    pub enum Recurrence {
+        #[default]
+        Week,
+        /// Every `weeks` weeks, counting from the week containing `start`
+        Weeks { weeks: usize, start: NaiveDate },
+        /// In the given weeks of each month, 1 to 5 or -1 for the last week
+        Month { weeks: Vec<i32> },
+    }
+
+    /// What a rule reminds of. Each category is scheduled on its own, so
+    /// alarms of different categories never merge
+    #[derive(
+        Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord,
+    )]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Category {
+        #[default]
+        Break,
+        /// 20-20-20 micro-breaks: look 20 feet away for 20 seconds
+        Eyes,
+        Stretch,
+        Water,
+        Custom,
+    }
+
+    impl Category {
+        pub const ALL: [Category; 5] = [
+            Category::Break,
+            Category::Eyes,
+            Category::Stretch,
+            Category::Water,
+            Category::Custom,
+        ];
+    }
+
+    /// Built in alert sounds
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Sound {
+        /// Five beeps
+        #[default]
+        Beeps,
+        /// A single beep
+        Beep,
+        /// A single quiet beep
+        Soft,
+        /// Two quick beeps
+        Double,
+        /// Three slow beeps
+        Triple,
+        /// Tones synthesised in turn
+        Tones(Vec<Tone>),
+        /// A message spoken by the text-to-speech engine, or a beep if
+        /// there is none
+        Speech(String),
+        /// A sound of the desktop's sound theme e.g. `bell`, `complete` or
+        /// `alarm-clock-elapsed`, or a beep if the theme has none
+        Theme(String),
+    }
+
+    /// A sine tone of a synthesised sound
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Tone {
+        //Hz
+        pub frequency: u32,
+        //milliseconds
+        pub duration: u64,
+        //silence after the tone in milliseconds
+        #[serde(default)]
+        pub gap: u64,
+    }
+
+    impl Tone {
+        pub fn validate(&self) -> Result<(), String> {
+            if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&self.frequency) {
+                return Err(format!(
+                    "Frequency {} Hz is not between {} and {} Hz",
+                    self.frequency, MIN_FREQUENCY, MAX_FREQUENCY
+                ));
+            }
+            if self.duration == 0 || self.duration > MAX_TONE_MS {
+                return Err(format!(
+                    "Duration {} ms is not between 1 and {} ms",
+                    self.duration, MAX_TONE_MS
+                ));
+            }
+            if self.gap > MAX_TONE_MS {
+                return Err(format!("Gap {} ms is over {} ms", self.gap, MAX_TONE_MS));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// How the alarms of a category are announced
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Reminder {
+        pub sound: Sound,
+        //shown by the UI when the alarm plays
+        pub text: String,
+        //length of the break in seconds, the category's default when missing
+        #[serde(default)]
+        pub duration: Option<usize>,
+    }
+
+    impl Reminder {
+        /// Sound and text used until the category is configured
+        pub fn default_for(category: &Category) -> Self {
+            let (sound, text) = match category {
+                Category::Break => (Sound::Beeps, "Time for a break"),
+                Category::Eyes => (Sound::Soft, "Look 20 feet away for 20 seconds"),
+                Category::Stretch => (Sound::Triple, "Stand up and stretch"),
+                Category::Water => (Sound::Double, "Drink some water"),
+                Category::Custom => (Sound::Beep, "Reminder"),
+            };
+
+            Self {
+                sound,
+                text: text.to_string(),
+                duration: None,
+            }
+        }
+
+        /// Fails on tones which can't be played or play for too long
+        pub fn validate(&self) -> Result<(), String> {
+            let tones = match &self.sound {
+                Sound::Tones(tones) if tones.is_empty() => return Err("No tones given".to_string()),
+                Sound::Tones(tones) => tones,
+                _ => return Ok(()),
+            };
+
+            tones.iter().try_for_each(Tone::validate)?;
+            let total: u64 = tones.iter().map(|t| t.duration + t.gap).sum();
+            if total > MAX_PATTERN_MS {
+                return Err(format!(
+                    "Tones play for {} ms, over {} ms",
+                    total, MAX_PATTERN_MS
+                ));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Something to do during a break, shown when the alarm plays
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Activity {
+        pub title: String,
+        pub text: String,
+        //seconds
+        pub duration: usize,
+        #[serde(default)]
+        pub image: Option<String>,
+        #[serde(default)]
+        pub category: Category,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    #[serde(rename_all = "kebab-case")]
     pub struct Rule {
         pub days: Vec<String>,
         pub from: usize,
         pub interval: usize,
         pub serial: usize,
         pub to: usize,
+        #[serde(default)]
+        pub kind: RuleKind,
+        //cron expression or RRULE for the corresponding kinds
+        #[serde(default)]
+        pub expression: Option<String>,
+        //applies to interval rules only
+        #[serde(default)]
+        pub anchor: Anchor,
+        #[serde(default)]
+        pub random: Option<Random>,
+        #[serde(default)]
+        pub ramp: Option<Ramp>,
+        //alarms of higher priority rules win when alarms are too close
+        #[serde(default)]
+        pub priority: usize,
+        //no alarms of the rule are played in these windows e.g. lunch
+        #[serde(default)]
+        pub exclusions: Vec<TimeWindow>,
+        //first and last dates on which the rule applies
+        #[serde(default)]
+        pub valid_from: Option<NaiveDate>,
+        #[serde(default)]
+        pub valid_until: Option<NaiveDate>,
+        #[serde(default)]
+        pub recurrence: Recurrence,
+        #[serde(default)]
+        pub category: Category,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct DateRange {
+        pub from: NaiveDate,
+        pub to: NaiveDate,
+    }
+
+    /// Rules which replace the weekday rules on a given date
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct DateOverride {
+        pub date: NaiveDate,
+        pub rules: Vec<Rule>,
+    }
+
+    /// Dates on which no alarms are played e.g. public holidays and vacations
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Exceptions {
+        #[serde(default)]
+        pub dates: Vec<NaiveDate>,
+        #[serde(default)]
+        pub ranges: Vec<DateRange>,
+        #[serde(default)]
+        pub holiday_file: Option<String>,
+        #[serde(default)]
+        pub overrides: Vec<DateOverride>,
+    }
+
+    /// What to do with alarms which fall inside a meeting
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum MeetingAction {
+        #[default]
+        Suppress,
+        /// Move the alarm to the end of the meeting
+        Defer,
+    }
+
+    /// Local iCalendar files whose events are treated as busy time
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    pub struct Meetings {
+        #[serde(default)]
+        pub files: Vec<String>,
+        #[serde(default)]
+        pub action: MeetingAction,
+    }
+
+    /// Phases of a Pomodoro cycle
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Phase {
+        #[default]
+        Work,
+        ShortBreak,
+        LongBreak,
+    }
+
+    /// Lengths of the Pomodoro phases in minutes. A long break follows
+    /// every `cycles` work blocks
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct PomodoroSettings {
+        pub work: usize,
+        pub short_break: usize,
+        pub long_break: usize,
+        pub cycles: usize,
+    }
+
+    impl Default for PomodoroSettings {
+        fn default() -> Self {
+            Self {
+                work: 25,
+                short_break: 5,
+                long_break: 15,
+                cycles: 4,
+            }
+        }
+    }
+
+    /// Micro-breaks played when keyboard or mouse use within `window`
+    /// minutes reaches a threshold. A threshold of 0 is not checked
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct IntensitySettings {
+        pub enabled: bool,
+        pub window: usize,
+        pub keystrokes: u64,
+        pub mouse: u64,
+    }
+
+    impl Default for IntensitySettings {
+        fn default() -> Self {
+            Self {
+                enabled: false,
+                window: 10,
+                keystrokes: 1500,
+                mouse: 50000,
+            }
+        }
+    }
+
+    /// Output device, by name, and volume in percent of the alerts. The
+    /// default device is used when none is chosen or it is missing
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct AudioSettings {
+        pub device: Option<String>,
+        pub volume: usize,
+    }
+
+    impl Default for AudioSettings {
+        fn default() -> Self {
+            Self {
+                device: None,
+                volume: 100,
+            }
+        }
+    }
+
+    /// Contents of settings.json
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Settings {
+        pub rules: Vec<Rule>,
+        #[serde(default)]
+        pub exceptions: Exceptions,
+        #[serde(default)]
+        pub meetings: Meetings,
+        /// Minimum number of minutes between breaks. 0 to allow any gap
+        #[serde(default)]
+        pub min_gap: usize,
+        /// No alarms of any rule are played in these windows
+        #[serde(default)]
+        pub quiet_hours: Vec<TimeWindow>,
+        #[serde(default)]
+        pub pomodoro: PomodoroSettings,
+        /// Sound and text of each category. Defaults apply to those missing
+        #[serde(default)]
+        pub reminders: HashMap<Category, Reminder>,
+        /// Added to the bundled activities, replacing those of the same title
+        #[serde(default)]
+        pub activities: Vec<Activity>,
+        #[serde(default)]
+        pub intensity: IntensitySettings,
+        /// Minutes of computer use a day before a strong alert. 0 for no limit
+        #[serde(default)]
+        pub daily_limit: usize,
+        #[serde(default)]
+        pub audio: AudioSettings,
+    }
+
+    impl Settings {
+        /// Reminder of the category. One saved with tones which can't be
+        /// played falls back to the default
+        pub fn reminder(&self, category: &Category) -> Reminder {
+            self.reminders
+                .get(category)
+                .filter(|r| r.validate().is_ok())
+                .cloned()
+                .unwrap_or_else(|| Reminder::default_for(category))
+        }
     }
 
     #[derive(Clone, Debug)]
     pub enum Payload {
-        Rules(Vec<Rule>),
-        Alarm(Option<Alarm>),
+        Settings(Box<Settings>),
+        //alarm being played, what to do during it and the break's length
+        //in seconds
+        Alarm(Alarm, Option<Activity>, usize),
+        //next alarm of each category which has one
+        NextAlarm(Vec<Alarm>, Vec<BusyBlock>),
+        Pomodoro(PomodoroStatus),
+        Stats(Stats),
+        //time and category of a timer to add
+        Timer(NaiveDateTime, Category),
+        TimerId(usize),
+        Timers(Vec<Timer>),
+        Minutes(usize),
+        Days(usize),
+        //minutes of use today and the daily limit
+        DailyLimit(usize, usize),
+        //minutes per day and whether they can be tracked here
+        ActiveTime(BTreeMap<NaiveDate, usize>, bool),
+        Alert(Alert),
+        Response(Response),
+        Focus(FocusStatus),
+        //feature which can't work and why
+        Unsupported(String, String),
+        Empty,
     }
 
     #[derive(Clone, Debug, PartialEq)]
@@ -51,22 +521,95 @@ mod ui_handler {
         //from the UI
         CmdStartup,
         CmdUpdateRules,
+        CmdUpdateExceptions,
+        CmdUpdateMeetings,
+        CmdSimulate,
+        CmdExportIcs,
+        CmdUpdateMinGap,
+        CmdUpdateQuietHours,
+        CmdUpdatePomodoro,
+        CmdPomodoroStart,
+        CmdPomodoroStop,
+        CmdPomodoroReset,
+        CmdUpdateReminders,
+        CmdUpdateActivities,
+        CmdAddTimer,
+        CmdCancelTimer,
+        CmdFocusStart,
+        CmdFocusExtend,
+        CmdFocusEnd,
+        CmdUpdateIntensity,
+        CmdUpdateDailyLimit,
+        CmdGetActiveTime,
+        CmdBreakAcknowledge,
+        CmdBreakSkip,
+        CmdUpdateAudio,
+        CmdGetAudioDevices,
         //For UI
         EvtRulesApplied,
+        EvtRulesInvalid,
+        EvtSettingsInvalid,
+        EvtExceptionsApplied,
+        EvtMeetingsApplied,
         EvtStarted,
+        EvtSimulation,
+        EvtIcsExported,
+        EvtMinGapApplied,
+        EvtQuietHoursApplied,
+        EvtPomodoroApplied,
+        EvtRemindersApplied,
+        EvtActivitiesApplied,
+        EvtTimerInvalid,
+        EvtIntensityApplied,
+        EvtDailyLimitApplied,
+        EvtAudioApplied,
+        EvtAudioDevices,
 
         //For alarm manager
         CmdUpdateAlarms,
         //From alarm manager
         EvtNextAlarm,
         EvtPlayingAlarm,
+        EvtPomodoro,
+        EvtStats,
+        EvtTimers,
+        EvtFocus,
+        EvtDailyLimit,
+        EvtActiveTime,
+        EvtAlert,
+        EvtAlertEnded,
+        EvtUnsupported,
     }
 
     impl MessageType {
         pub fn from_str(typ: &str) -> Option<Self> {
             match typ {
                 "cmd-update-rules" => Some(MessageType::CmdUpdateRules),
+                "cmd-update-exceptions" => Some(MessageType::CmdUpdateExceptions),
+                "cmd-update-meetings" => Some(MessageType::CmdUpdateMeetings),
                 "cmd-startup" => Some(MessageType::CmdStartup),
+                "cmd-simulate" => Some(MessageType::CmdSimulate),
+                "cmd-export-ics" => Some(MessageType::CmdExportIcs),
+                "cmd-update-min-gap" => Some(MessageType::CmdUpdateMinGap),
+                "cmd-update-quiet-hours" => Some(MessageType::CmdUpdateQuietHours),
+                "cmd-update-pomodoro" => Some(MessageType::CmdUpdatePomodoro),
+                "cmd-pomodoro-start" => Some(MessageType::CmdPomodoroStart),
+                "cmd-pomodoro-stop" => Some(MessageType::CmdPomodoroStop),
+                "cmd-pomodoro-reset" => Some(MessageType::CmdPomodoroReset),
+                "cmd-update-reminders" => Some(MessageType::CmdUpdateReminders),
+                "cmd-update-activities" => Some(MessageType::CmdUpdateActivities),
+                "cmd-add-timer" => Some(MessageType::CmdAddTimer),
+                "cmd-cancel-timer" => Some(MessageType::CmdCancelTimer),
+                "cmd-focus-start" => Some(MessageType::CmdFocusStart),
+                "cmd-focus-extend" => Some(MessageType::CmdFocusExtend),
+                "cmd-focus-end" => Some(MessageType::CmdFocusEnd),
+                "cmd-update-intensity" => Some(MessageType::CmdUpdateIntensity),
+                "cmd-update-daily-limit" => Some(MessageType::CmdUpdateDailyLimit),
+                "cmd-get-active-time" => Some(MessageType::CmdGetActiveTime),
+                "cmd-break-acknowledge" => Some(MessageType::CmdBreakAcknowledge),
+                "cmd-break-skip" => Some(MessageType::CmdBreakSkip),
+                "cmd-update-audio" => Some(MessageType::CmdUpdateAudio),
+                "cmd-get-audio-devices" => Some(MessageType::CmdGetAudioDevices),
                 _ => None,
             }
         }
@@ -78,6 +621,32 @@ mod ui_handler {
                 MessageType::EvtNextAlarm => write!(f, "event-next-alarm"),
                 MessageType::EvtStarted => write!(f, "event-started"),
                 MessageType::EvtRulesApplied => write!(f, "event-rules-applied"),
+                MessageType::EvtRulesInvalid => write!(f, "event-rules-invalid"),
+                MessageType::EvtSettingsInvalid => write!(f, "event-settings-invalid"),
+                MessageType::EvtExceptionsApplied => write!(f, "event-exceptions-applied"),
+                MessageType::EvtMeetingsApplied => write!(f, "event-meetings-applied"),
+                MessageType::EvtSimulation => write!(f, "event-simulation"),
+                MessageType::EvtIcsExported => write!(f, "event-ics-exported"),
+                MessageType::EvtMinGapApplied => write!(f, "event-min-gap-applied"),
+                MessageType::EvtQuietHoursApplied => write!(f, "event-quiet-hours-applied"),
+                MessageType::EvtPomodoroApplied => write!(f, "event-pomodoro-applied"),
+                MessageType::EvtPomodoro => write!(f, "event-pomodoro"),
+                MessageType::EvtPlayingAlarm => write!(f, "event-playing-alarm"),
+                MessageType::EvtRemindersApplied => write!(f, "event-reminders-applied"),
+                MessageType::EvtStats => write!(f, "event-stats"),
+                MessageType::EvtActivitiesApplied => write!(f, "event-activities-applied"),
+                MessageType::EvtTimerInvalid => write!(f, "event-timer-invalid"),
+                MessageType::EvtTimers => write!(f, "event-timers"),
+                MessageType::EvtFocus => write!(f, "event-focus"),
+                MessageType::EvtIntensityApplied => write!(f, "event-intensity-applied"),
+                MessageType::EvtDailyLimitApplied => write!(f, "event-daily-limit-applied"),
+                MessageType::EvtDailyLimit => write!(f, "event-daily-limit"),
+                MessageType::EvtActiveTime => write!(f, "event-active-time"),
+                MessageType::EvtAlert => write!(f, "event-alert"),
+                MessageType::EvtAlertEnded => write!(f, "event-alert-ended"),
+                MessageType::EvtUnsupported => write!(f, "event-unsupported"),
+                MessageType::EvtAudioApplied => write!(f, "event-audio-applied"),
+                MessageType::EvtAudioDevices => write!(f, "event-audio-devices"),
                 _ => write!(f, "not-implemented"),
             }
         }
@@ -92,8 +661,10 @@ mod ui_handler {
     #[derive(Clone, Debug, PartialEq)]
     pub struct Alarm {
         pub day: Weekday,
+        pub date: NaiveDate,
         pub hour: usize,
         pub min: usize,
+        pub category: Category,
     }
 
     impl Serialize for Alarm {
@@ -101,10 +672,12 @@ mod ui_handler {
         where
             S: Serializer,
         {
-            let mut state = serializer.serialize_struct("Alarm", 3)?;
+            let mut state = serializer.serialize_struct("Alarm", 5)?;
             state.serialize_field("day", &format!("{:?}", self.day))?;
+            state.serialize_field("date", &self.date.format("%Y-%m-%d").to_string())?;
             state.serialize_field("hour", &self.hour)?;
             state.serialize_field("min", &self.min)?;
+            state.serialize_field("category", &self.category)?;
             state.end()
         }
     }
@@ -116,7 +689,7 @@ mod ui_handler {
         //for managing communication with alarm manager
         am_tx: BcastSender<Message>,
         am_rx: BcastReceiver<Message>,
-        rules: Vec<Rule>,
+        settings: Settings,
         prev_alarm: Option<Alarm>,
     }
 
@@ -125,20 +698,20 @@ mod ui_handler {
             let (am_tx, am_rx): (BcastSender<Message>, BcastReceiver<Message>) =
                 broadcast::channel(BCAST_CHANNEL_SIZE);
 
-            let rules = Self::read_rules();
+            let settings = Self::read_settings();
 
             Self {
                 ui_rx,
                 win_handle,
                 am_tx,
                 am_rx,
-                rules,
+                settings,
                 prev_alarm: None,
             }
         }
 
         pub fn run(mut self) {
-            let am = AlarmManager::new(self.am_tx.clone(), self.am_tx.subscribe());
+            let am = AlarmManager::new(self.am_tx.clone(), self.am_tx.subscribe(), &self.settings);
 
             // Start AlarmManager inside Tauri runtime
             tauri::async_runtime::spawn(async move {
@@ -168,22 +741,42 @@ mod ui_handler {
             match msg.typ {
                 MessageType::EvtNextAlarm => self.handle_next_alarm(msg.payload),
                 MessageType::EvtPlayingAlarm => self.handle_playing_alarm(msg.payload),
+                MessageType::EvtPomodoro => self.handle_pomodoro_status(msg.payload),
+                MessageType::EvtStats => self.handle_stats(msg.payload),
+                MessageType::EvtTimers => self.handle_timers(msg.payload),
+                MessageType::EvtFocus => self.handle_focus(msg.payload),
+                MessageType::EvtDailyLimit => self.handle_daily_limit(msg.payload),
+                MessageType::EvtActiveTime => self.handle_active_time(msg.payload),
+                MessageType::EvtAlert => self.handle_alert(msg.payload),
+                MessageType::EvtAlertEnded => self.handle_alert_ended(msg.payload),
+                MessageType::EvtUnsupported => self.handle_unsupported(msg.payload),
                 _ => (),
             }
         }
 
+        /// `next-alarm` is the next break, `next-alarms` the next alarm
+        /// of each category
         fn handle_next_alarm(&self, payload: Payload) {
             let json: Value = match payload {
-                Payload::Alarm(alarm) => match alarm {
-                    Some(alarm) => json!({
-                        "next-alarm": alarm,
-                        "prev-alarm": self.prev_alarm
-                    }),
-                    None => json!({
-                        "next-alarm": null,
-                        "prev-alarm": null
-                    }),
-                },
+                Payload::NextAlarm(alarms, busy) => {
+                    let by_category: HashMap<&Category, &Alarm> =
+                        alarms.iter().map(|a| (&a.category, a)).collect();
+
+                    match by_category.get(&Category::Break) {
+                        Some(alarm) => json!({
+                            "next-alarm": alarm,
+                            "next-alarms": by_category,
+                            "prev-alarm": self.prev_alarm,
+                            "busy": busy
+                        }),
+                        None => json!({
+                            "next-alarm": null,
+                            "next-alarms": by_category,
+                            "prev-alarm": null,
+                            "busy": busy
+                        }),
+                    }
+                }
                 _ => return,
             };
 
@@ -192,9 +785,119 @@ mod ui_handler {
                 .unwrap();
         }
 
+        /// Sends the alarm with its category's text and the activity picked
+        /// for it to the UI
         fn handle_playing_alarm(&mut self, payload: Payload) {
-            if let Payload::Alarm(Some(alarm)) = payload {
-                self.prev_alarm = Some(alarm);
+            if let Payload::Alarm(alarm, activity, seconds) = payload {
+                let json = json!({
+                    "alarm": alarm,
+                    "text": self.settings.reminder(&alarm.category).text,
+                    "activity": activity,
+                    "seconds": seconds
+                });
+
+                self.win_handle
+                    .emit(&MessageType::EvtPlayingAlarm.to_string(), json.to_string())
+                    .unwrap();
+
+                if alarm.category == Category::Break {
+                    self.prev_alarm = Some(alarm);
+                }
+            }
+        }
+
+        fn handle_pomodoro_status(&self, payload: Payload) {
+            if let Payload::Pomodoro(status) = payload {
+                let json = json!({ "pomodoro": status });
+
+                self.win_handle
+                    .emit(&MessageType::EvtPomodoro.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_stats(&self, payload: Payload) {
+            if let Payload::Stats(stats) = payload {
+                let json = json!({ "stats": stats });
+
+                self.win_handle
+                    .emit(&MessageType::EvtStats.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_timers(&self, payload: Payload) {
+            if let Payload::Timers(timers) = payload {
+                let json = json!({ "timers": timers });
+
+                self.win_handle
+                    .emit(&MessageType::EvtTimers.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_focus(&self, payload: Payload) {
+            if let Payload::Focus(status) = payload {
+                let json = json!({ "focus": status });
+
+                self.win_handle
+                    .emit(&MessageType::EvtFocus.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_daily_limit(&self, payload: Payload) {
+            if let Payload::DailyLimit(minutes, limit) = payload {
+                let json = json!({ "minutes": minutes, "limit": limit });
+
+                self.win_handle
+                    .emit(&MessageType::EvtDailyLimit.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_active_time(&self, payload: Payload) {
+            if let Payload::ActiveTime(days, supported) = payload {
+                let json = json!({
+                    "active-time": days,
+                    "supported": supported,
+                    "daily-limit": self.settings.daily_limit
+                });
+
+                self.win_handle
+                    .emit(&MessageType::EvtActiveTime.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        /// The UI shows a notification or an overlay depending on the stage
+        fn handle_alert(&self, payload: Payload) {
+            if let Payload::Alert(alert) = payload {
+                let json = json!({ "alert": alert, "stage": alert.stage() });
+
+                self.win_handle
+                    .emit(&MessageType::EvtAlert.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_alert_ended(&self, payload: Payload) {
+            if let Payload::Response(response) = payload {
+                let json = json!({ "response": response });
+
+                self.win_handle
+                    .emit(&MessageType::EvtAlertEnded.to_string(), json.to_string())
+                    .unwrap();
+            }
+        }
+
+        fn handle_unsupported(&self, payload: Payload) {
+            if let Payload::Unsupported(feature, reason) = payload {
+                let json = json!({ "feature": feature, "error": reason });
+
+                self.win_handle
+                    .emit(&MessageType::EvtUnsupported.to_string(), json.to_string())
+                    .unwrap();
             }
         }
 
@@ -207,22 +910,54 @@ mod ui_handler {
             if let Some(typ) = json.get("type").and_then(|n| n.as_str()) {
                 match MessageType::from_str(typ) {
                     Some(MessageType::CmdUpdateRules) => self.handle_update_rules(json),
+                    Some(MessageType::CmdUpdateExceptions) => self.handle_update_exceptions(json),
+                    Some(MessageType::CmdUpdateMeetings) => self.handle_update_meetings(json),
                     Some(MessageType::CmdStartup) => self.handle_startup(),
+                    Some(MessageType::CmdSimulate) => self.handle_simulate(json),
+                    Some(MessageType::CmdExportIcs) => self.handle_export_ics(json),
+                    Some(MessageType::CmdUpdateMinGap) => self.handle_update_min_gap(json),
+                    Some(MessageType::CmdUpdateQuietHours) => self.handle_update_quiet_hours(json),
+                    Some(MessageType::CmdUpdatePomodoro) => self.handle_update_pomodoro(json),
+                    Some(MessageType::CmdUpdateReminders) => self.handle_update_reminders(json),
+                    Some(MessageType::CmdUpdateActivities) => self.handle_update_activities(json),
+                    Some(MessageType::CmdUpdateIntensity) => self.handle_update_intensity(json),
+                    Some(MessageType::CmdUpdateDailyLimit) => self.handle_update_daily_limit(json),
+                    Some(MessageType::CmdGetActiveTime) => self.handle_get_active_time(json),
+                    Some(MessageType::CmdUpdateAudio) => self.handle_update_audio(json),
+                    Some(MessageType::CmdGetAudioDevices) => self.handle_get_audio_devices(),
+                    Some(MessageType::CmdAddTimer) => self.handle_add_timer(json),
+                    Some(MessageType::CmdCancelTimer) => self.handle_cancel_timer(json),
+                    Some(
+                        typ @ (MessageType::CmdPomodoroStart
+                        | MessageType::CmdPomodoroStop
+                        | MessageType::CmdPomodoroReset
+                        | MessageType::CmdBreakAcknowledge
+                        | MessageType::CmdBreakSkip),
+                    ) => self.forward_to_am(typ),
+                    Some(typ @ (MessageType::CmdFocusStart | MessageType::CmdFocusExtend)) => {
+                        self.forward_minutes(typ, json)
+                    }
+                    Some(MessageType::CmdFocusEnd) => self.forward_to_am(MessageType::CmdFocusEnd),
                     _ => (),
                 }
             }
         }
 
         fn handle_startup(&self) {
-            let c = Message {
-                typ: MessageType::CmdUpdateAlarms,
-                payload: Payload::Rules(self.rules.clone()),
-            };
-
-            self.am_tx.send(c).unwrap();
+            self.update_alarms();
 
             let json = json!({
-                "rules": serde_json::to_string(&self.rules).unwrap()
+                "rules": serde_json::to_string(&self.settings.rules).unwrap(),
+                "exceptions": serde_json::to_string(&self.settings.exceptions).unwrap(),
+                "meetings": serde_json::to_string(&self.settings.meetings).unwrap(),
+                "min-gap": self.settings.min_gap,
+                "quiet-hours": serde_json::to_string(&self.settings.quiet_hours).unwrap(),
+                "pomodoro": serde_json::to_string(&self.settings.pomodoro).unwrap(),
+                "reminders": serde_json::to_string(&self.reminders()).unwrap(),
+                "activities": serde_json::to_string(&self.settings.activities).unwrap(),
+                "intensity": serde_json::to_string(&self.settings.intensity).unwrap(),
+                "daily-limit": self.settings.daily_limit,
+                "audio": serde_json::to_string(&self.settings.audio).unwrap()
             });
 
             self.win_handle
@@ -230,43 +965,479 @@ mod ui_handler {
                 .unwrap();
         }
 
+        /// Applies the rules sent by the UI. If any of them is invalid,
+        /// none are applied and the errors are sent back instead
         fn handle_update_rules(&mut self, json: serde_json::Value) {
             let mut rule_objects: Vec<Rule> = Vec::new();
+            let mut errors: Vec<Value> = Vec::new();
 
             if let Some(rules) = json.get("rules").and_then(serde_json::Value::as_array) {
                 for rule_json in rules {
-                    let rule: Rule = serde_json::from_value(rule_json.clone())
-                        .expect("Rule deserialization error");
-                    rule_objects.push(rule);
+                    let serial = rule_json.get("serial").cloned().unwrap_or(Value::Null);
+                    let result = serde_json::from_value::<Rule>(rule_json.clone())
+                        .map_err(|e| e.to_string())
+                        .and_then(|rule| validate_rule(&rule).map(|_| rule));
+
+                    match result {
+                        Ok(rule) => rule_objects.push(rule),
+                        Err(e) => errors.push(json!({ "serial": serial, "error": e })),
+                    }
+                }
+            }
+
+            if !errors.is_empty() {
+                let json = json!({ "errors": errors });
+
+                self.win_handle
+                    .emit(&MessageType::EvtRulesInvalid.to_string(), json.to_string())
+                    .unwrap();
+                return;
+            }
+
+            self.settings.rules = rule_objects;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "rules": serde_json::to_string(&self.settings.rules).unwrap()
+            });
+
+            self.win_handle
+                .emit(&MessageType::EvtRulesApplied.to_string(), json.to_string())
+                .unwrap();
+        }
+
+        fn handle_update_exceptions(&mut self, json: serde_json::Value) {
+            let exceptions: Exceptions = match self.parse_setting(&json, "exceptions") {
+                Some(e) => e,
+                None => return,
+            };
+
+            self.settings.exceptions = exceptions;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "exceptions": serde_json::to_string(&self.settings.exceptions).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtExceptionsApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn handle_update_meetings(&mut self, json: serde_json::Value) {
+            let meetings: Meetings = match self.parse_setting(&json, "meetings") {
+                Some(m) => m,
+                None => return,
+            };
+
+            self.settings.meetings = meetings;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "meetings": serde_json::to_string(&self.settings.meetings).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtMeetingsApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn handle_update_min_gap(&mut self, json: serde_json::Value) {
+            let min_gap = match json.get("min-gap").and_then(|g| g.as_u64()) {
+                Some(g) => g as usize,
+                None => return,
+            };
+
+            self.settings.min_gap = min_gap;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({ "min-gap": self.settings.min_gap });
+
+            self.win_handle
+                .emit(&MessageType::EvtMinGapApplied.to_string(), json.to_string())
+                .unwrap();
+        }
+
+        fn handle_update_daily_limit(&mut self, json: serde_json::Value) {
+            let daily_limit = match json.get("daily-limit").and_then(|l| l.as_u64()) {
+                Some(l) => l as usize,
+                None => return,
+            };
+
+            self.settings.daily_limit = daily_limit;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({ "daily-limit": self.settings.daily_limit });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtDailyLimitApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        /// Asks the alarm manager for the minutes of use on each of the last
+        /// `days` days, a week unless given
+        fn handle_get_active_time(&self, json: serde_json::Value) {
+            let days = json.get("days").and_then(|d| d.as_u64()).unwrap_or(7);
+
+            let c = Message {
+                typ: MessageType::CmdGetActiveTime,
+                payload: Payload::Days(days as usize),
+            };
+
+            self.am_tx.send(c).unwrap();
+        }
+
+        fn handle_update_audio(&mut self, json: serde_json::Value) {
+            let audio: AudioSettings = match self.parse_setting(&json, "audio") {
+                Some(a) => a,
+                None => return,
+            };
+
+            self.settings.audio = audio;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "audio": serde_json::to_string(&self.settings.audio).unwrap()
+            });
+
+            self.win_handle
+                .emit(&MessageType::EvtAudioApplied.to_string(), json.to_string())
+                .unwrap();
+        }
+
+        fn handle_get_audio_devices(&self) {
+            let json = json!({
+                "devices": output_devices(),
+                "device": self.settings.audio.device
+            });
+
+            self.win_handle
+                .emit(&MessageType::EvtAudioDevices.to_string(), json.to_string())
+                .unwrap();
+        }
+
+        fn handle_update_quiet_hours(&mut self, json: serde_json::Value) {
+            let quiet_hours: Vec<TimeWindow> = match self.parse_setting(&json, "quiet-hours") {
+                Some(q) => q,
+                None => return,
+            };
+
+            self.settings.quiet_hours = quiet_hours;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "quiet-hours": serde_json::to_string(&self.settings.quiet_hours).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtQuietHoursApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn handle_update_pomodoro(&mut self, json: serde_json::Value) {
+            let pomodoro: PomodoroSettings = match self.parse_setting(&json, "pomodoro") {
+                Some(p) => p,
+                None => return,
+            };
+
+            self.settings.pomodoro = pomodoro;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "pomodoro": serde_json::to_string(&self.settings.pomodoro).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtPomodoroApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn handle_update_reminders(&mut self, json: serde_json::Value) {
+            let reminders: HashMap<Category, Reminder> =
+                match self.parse_setting(&json, "reminders") {
+                    Some(r) => r,
+                    None => return,
+                };
+            if let Some(e) = reminders.values().find_map(|r| r.validate().err()) {
+                self.notify_invalid("reminders", e);
+                return;
+            }
+
+            self.settings.reminders = reminders;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "reminders": serde_json::to_string(&self.reminders()).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtRemindersApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn handle_update_activities(&mut self, json: serde_json::Value) {
+            let activities: Vec<Activity> = match self.parse_setting(&json, "activities") {
+                Some(a) => a,
+                None => return,
+            };
+
+            self.settings.activities = activities;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "activities": serde_json::to_string(&self.settings.activities).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtActivitiesApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn handle_update_intensity(&mut self, json: serde_json::Value) {
+            let intensity: IntensitySettings = match self.parse_setting(&json, "intensity") {
+                Some(i) => i,
+                None => return,
+            };
+
+            self.settings.intensity = intensity;
+            Self::save_settings(&self.settings);
+            self.update_alarms();
+
+            let json = json!({
+                "intensity": serde_json::to_string(&self.settings.intensity).unwrap()
+            });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtIntensityApplied.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        /// Adds a timer going off in `minutes` or `at` a time today
+        fn handle_add_timer(&self, json: serde_json::Value) {
+            let now = Local::now().naive_local();
+            let timer = match json.get("category") {
+                Some(c) => {
+                    serde_json::from_value(c.clone()).map_err(|_| format!("Unknown category {}", c))
                 }
+                None => Ok(Category::Break),
+            }
+            .and_then(|category| timer_at(&json, now).map(|at| (at, category)));
+
+            match timer {
+                Ok((at, category)) => {
+                    let c = Message {
+                        typ: MessageType::CmdAddTimer,
+                        payload: Payload::Timer(at, category),
+                    };
+
+                    self.am_tx.send(c).unwrap();
+                }
+                Err(e) => {
+                    let json = json!({ "error": e });
+
+                    self.win_handle
+                        .emit(&MessageType::EvtTimerInvalid.to_string(), json.to_string())
+                        .unwrap();
+                }
+            }
+        }
+
+        fn handle_cancel_timer(&self, json: serde_json::Value) {
+            if let Some(id) = json.get("id").and_then(serde_json::Value::as_u64) {
+                let c = Message {
+                    typ: MessageType::CmdCancelTimer,
+                    payload: Payload::TimerId(id as usize),
+                };
+
+                self.am_tx.send(c).unwrap();
+            }
+        }
+
+        /// Reads the setting under `key`. A malformed one is reported to the
+        /// UI, like invalid rules, and None returned
+        fn parse_setting<T: DeserializeOwned>(&self, json: &Value, key: &str) -> Option<T> {
+            let result = serde_json::from_value(json.get(key)?.clone());
+
+            match result {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    self.notify_invalid(key, e.to_string());
+                    None
+                }
+            }
+        }
+
+        fn notify_invalid(&self, key: &str, error: String) {
+            let json = json!({ "setting": key, "error": error });
+
+            self.win_handle
+                .emit(
+                    &MessageType::EvtSettingsInvalid.to_string(),
+                    json.to_string(),
+                )
+                .unwrap();
+        }
+
+        /// Reminders of all categories, including the defaults
+        fn reminders(&self) -> HashMap<Category, Reminder> {
+            Category::ALL
+                .iter()
+                .map(|c| (c.clone(), self.settings.reminder(c)))
+                .collect()
+        }
+
+        /// Passes a command with a number of `minutes` on to the alarm manager
+        fn forward_minutes(&self, typ: MessageType, json: serde_json::Value) {
+            let minutes = json.get("minutes").and_then(serde_json::Value::as_u64);
+            if let Some(minutes) = minutes.filter(|m| *m > 0) {
+                let c = Message {
+                    typ,
+                    payload: Payload::Minutes(minutes as usize),
+                };
+
+                self.am_tx.send(c).unwrap();
             }
+        }
 
-            Self::save_rules(&rule_objects);
+        /// Passes a command without arguments on to the alarm manager
+        fn forward_to_am(&self, typ: MessageType) {
+            let c = Message {
+                typ,
+                payload: Payload::Empty,
+            };
 
+            self.am_tx.send(c).unwrap();
+        }
+
+        fn update_alarms(&self) {
             let c = Message {
                 typ: MessageType::CmdUpdateAlarms,
-                payload: Payload::Rules(rule_objects.clone()),
+                payload: Payload::Settings(Box::new(self.settings.clone())),
             };
 
             self.am_tx.send(c).unwrap();
-            self.rules = rule_objects;
+        }
+
+        /// Dry-runs the current rules over the dates given by the UI.
+        /// Both `from` and `to` are inclusive and formatted as YYYY-MM-DD
+        fn handle_simulate(&self, json: serde_json::Value) {
+            let date = |key: &str| {
+                json.get(key)
+                    .and_then(|d| d.as_str())
+                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            };
+
+            let (from, to) = match (date("from"), date("to")) {
+                (Some(from), Some(to)) => (from, to + chrono::Duration::days(1)),
+                _ => return,
+            };
+            //spans e.g. of snoozing during which alarms show as paused
+            let pauses: Vec<Pause> = match json.get("pauses") {
+                Some(_) => match self.parse_setting(&json, "pauses") {
+                    Some(pauses) => pauses,
+                    None => return,
+                },
+                None => Vec::new(),
+            };
+
+            let midnight = |d: NaiveDate| {
+                Local
+                    .from_local_datetime(&d.and_hms_opt(0, 0, 0).unwrap())
+                    .earliest()
+            };
+
+            let events = match (midnight(from), midnight(to)) {
+                (Some(from), Some(to)) => {
+                    let busy = read_busy_blocks(
+                        &self.settings.meetings.files,
+                        &Local,
+                        from.naive_local(),
+                        to.naive_local(),
+                    );
+
+                    let mut events = Vec::new();
+                    for category in Category::ALL.iter() {
+                        let mut schedule = Schedule::for_category(&self.settings, category);
+                        schedule.set_busy(busy.clone());
+                        events.extend(simulate(&schedule, from, to, &pauses));
+                    }
+
+                    events.sort_by_key(|e| e.at);
+                    events
+                }
+                _ => return,
+            };
+
+            let json = json!({ "events": events });
+
+            self.win_handle
+                .emit(&MessageType::EvtSimulation.to_string(), json.to_string())
+                .unwrap();
+        }
+
+        /// Writes the rules as an iCalendar file to the path chosen in the UI
+        fn handle_export_ics(&self, json: serde_json::Value) {
+            let path = match json.get("path").and_then(|p| p.as_str()) {
+                Some(path) => path,
+                None => return,
+            };
+
+            let (ics, left_out) =
+                export_ics(&self.settings.rules, Local::now().date_naive(), Utc::now());
+            let error = std::fs::write(path, ics).err().map(|e| e.to_string());
 
             let json = json!({
-                "rules": serde_json::to_string(&self.rules).unwrap()
+                "path": path,
+                "error": error,
+                "left-out": left_out
             });
 
             self.win_handle
-                .emit(&MessageType::EvtRulesApplied.to_string(), json.to_string())
+                .emit(&MessageType::EvtIcsExported.to_string(), json.to_string())
                 .unwrap();
         }
 
-        fn save_rules(rules: &Vec<Rule>) {
-            let serialized_rules = serde_json::to_string(&rules).unwrap();
+        fn save_settings(settings: &Settings) {
+            let serialized_settings = serde_json::to_string(&settings).unwrap();
             let mut file = File::create(get_settings_file_name()).unwrap();
-            file.write_all(serialized_rules.as_bytes()).unwrap();
+            file.write_all(serialized_settings.as_bytes()).unwrap();
         }
 
-        fn read_rules() -> Vec<Rule> {
+        fn read_settings() -> Settings {
             let path = get_settings_file_name();
 
             // Ensure parent directory exists
@@ -285,10 +1456,18 @@ mod ui_handler {
             file.read_to_string(&mut contents).unwrap();
 
             if contents.trim().is_empty() {
-                Vec::new()
-            } else {
-                serde_json::from_str(&contents).unwrap_or_else(|_| Vec::new())
+                return Settings::default();
             }
+
+            //older versions saved just the list of rules
+            if let Ok(rules) = serde_json::from_str::<Vec<Rule>>(&contents) {
+                return Settings {
+                    rules,
+                    ..Default::default()
+                };
+            }
+
+            serde_json::from_str(&contents).unwrap_or_default()
         }
     }
 }
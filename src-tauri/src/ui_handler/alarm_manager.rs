@@ -4,36 +4,145 @@
 //! The tread runs throughout the life of the app. No need to handle
 //! shutdown
 
+mod activities;
 mod alarm_utils;
+mod calendar;
+mod escalation;
+mod expression;
+mod focus;
+mod ics_export;
+mod intensity;
+mod pomodoro;
+mod ramp;
+mod random;
+mod rrule;
+mod simulator;
+mod speech;
+mod stats;
+mod timers;
 mod alarm_manager {
 
+    use super::activities::*;
     use super::alarm_utils::*;
-    use crate::player::play;
+    use super::calendar::*;
+    use super::escalation::*;
+    use super::focus::*;
+    use super::intensity::*;
+    use super::pomodoro::*;
+    use super::speech;
+    use super::stats::*;
+    use super::timers::*;
+    use crate::player::{play_chime, play_escalation, play_limit, play_phase, play_sound};
     // use crate::Alarm;
     // use crate::{BcastReceiver, BcastSender, Message, MessageType, Payload};
-    use crate::ui_handler::{Alarm, BcastReceiver, BcastSender, Message, MessageType, Payload};
+    use crate::ui_handler::{
+        Activity, Alarm, AudioSettings, BcastReceiver, BcastSender, Category, IntensitySettings,
+        Message, MessageType, Payload, PomodoroSettings, Reminder, Settings, Sound,
+    };
+    use crate::utils::{
+        get_focus_file_name, get_pomodoro_file_name, get_shown_file_name, get_stats_file_name,
+        get_timers_file_name, idle_seconds, is_locked, LockedState,
+    };
 
-    use chrono::{offset::Local, Datelike, Timelike, Weekday};
-    use std::time::Duration;
+    use chrono::{offset::Local, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::collections::HashMap;
+    use std::time::{Duration, SystemTime};
 
     use log::debug;
-    use std::collections::HashMap;
+
+    //busy blocks are read for today and the coming week
+    const BUSY_WINDOW_DAYS: i64 = 8;
+
+    //default lengths of the breaks of categories other than Break, which
+    //lasts BREAK_MINUTES. The chime ending a 20-20-20 micro-break follows
+    //its alert after its length
+    const MICRO_BREAK_SECONDS: usize = 20;
+    const STRETCH_SECONDS: usize = 120;
+    const SHORT_BREAK_SECONDS: usize = 60;
+
+    //without input for longer the computer counts as not in use
+    const IDLE_SECONDS: u64 = 120;
 
     pub struct AlarmManager {
         tx: BcastSender<Message>,
         rx: BcastReceiver<Message>,
-        alarms: HashMap<Weekday, HashMap<usize, Vec<usize>>>,
+        //each category is scheduled on its own
+        schedules: HashMap<Category, Schedule>,
+        reminders: HashMap<Category, Reminder>,
+        audio: AudioSettings,
+        meeting_files: Vec<String>,
+        //date and modification times of meeting_files when last read
+        calendar_stamp: Option<(NaiveDate, Vec<Option<SystemTime>>)>,
+        //breaks skipped today
+        skipped: Vec<NaiveDateTime>,
+        pomodoro: Pomodoro,
+        pomodoro_settings: PomodoroSettings,
+        stats: Stats,
+        activities: Vec<Activity>,
+        //titles of the activities shown, most recent last. Saved so that
+        //activities don't repeat after a restart
+        shown: Vec<String>,
+        //one-shot alarms, on top of those of the rules
+        timers: Timers,
+        focus: Option<Focus>,
+        //reads the keyboard and mouse while intensity micro-breaks are enabled
+        monitor: Option<Box<dyn InputMonitor>>,
+        intensity: Intensity,
+        intensity_settings: IntensitySettings,
+        daily_limit: usize,
+        //date on which the daily limit was last alerted. Not saved, a use
+        //already past the limit at start counts as alerted
+        limit_reached: Option<NaiveDate>,
+        //break alerting until it is acknowledged or skipped
+        alert: Option<Alert>,
     }
 
     impl AlarmManager {
-        pub fn new(tx: BcastSender<Message>, rx: BcastReceiver<Message>) -> Self {
-            let alarms: HashMap<Weekday, HashMap<usize, Vec<usize>>> = HashMap::new();
-            Self { tx, rx, alarms }
+        /// Starts off with the saved settings, so that a Pomodoro phase
+        /// restored from the previous run ends after the right length even
+        /// before the UI sends the settings
+        pub fn new(
+            tx: BcastSender<Message>,
+            rx: BcastReceiver<Message>,
+            settings: &Settings,
+        ) -> Self {
+            let stats: Stats = load(&get_stats_file_name());
+            let today = Local::now().date_naive();
+            let limit_reached = (settings.daily_limit > 0
+                && stats.active.get(&today).copied().unwrap_or_default() >= settings.daily_limit)
+                .then_some(today);
+
+            Self {
+                tx,
+                rx,
+                schedules: schedules(settings),
+                reminders: reminders(settings),
+                audio: settings.audio.clone(),
+                meeting_files: Vec::new(),
+                calendar_stamp: None,
+                skipped: Vec::new(),
+                pomodoro: load(&get_pomodoro_file_name()),
+                pomodoro_settings: settings.pomodoro.clone(),
+                stats,
+                activities: library(&settings.activities),
+                shown: load(&get_shown_file_name()),
+                timers: load(&get_timers_file_name()),
+                focus: load(&get_focus_file_name()),
+                monitor: None,
+                intensity: Intensity::default(),
+                intensity_settings: settings.intensity.clone(),
+                daily_limit: settings.daily_limit,
+                limit_reached,
+                alert: None,
+            }
         }
 
         pub fn run(mut self) {
             tokio::spawn(async move {
                 loop {
+                    let phase_end = self.pomodoro.until_end(Utc::now()).unwrap_or_default();
+
                     tokio::select! {
                         msg = self.rx.recv() => {
                             match msg {
@@ -45,42 +154,219 @@ mod alarm_manager {
                         _ = tokio::time::sleep(Duration::from_secs(60)) => {
                             self.handle_timer_expiry();
                         }
+
+                        _ = tokio::time::sleep(phase_end), if self.pomodoro.is_running() => {
+                            self.handle_phase_end();
+                        }
                     }
                 }
             });
         }
 
         /// Recurring 1 minute timer
-        fn handle_timer_expiry(&self) {
+        fn handle_timer_expiry(&mut self) {
             debug!("alarm_manager: timer expiry");
+            self.refresh_busy_blocks();
+
             let now = Local::now();
-            let current_weekday: Weekday = now.weekday();
+            let today = now.date_naive();
             let current_hour: usize = now.hour() as usize;
             let current_minute: usize = now.minute() as usize;
+            self.skipped.retain(|t| t.date() == today);
+            self.set_skipped();
+            let active = self.track_active(today);
+            self.escalate_alert();
 
-            if let Some(hour_map) = self.alarms.get(&current_weekday) {
-                if let Some(minute_vec) = hour_map.get(&current_hour) {
-                    if minute_vec.contains(&current_minute) {
-                        debug!("alarm_manager: playing alarm");
-                        play();
+            let at = today
+                .and_hms_opt(current_hour as u32, current_minute as u32, 0)
+                .unwrap();
 
-                        let c = Message {
-                            typ: MessageType::EvtPlayingAlarm,
-                            payload: Payload::Alarm(Some(Alarm {
-                                day: current_weekday,
-                                hour: current_hour,
-                                min: current_minute,
-                            })),
-                        };
+            //the break ending a focus session covers one due at the same time
+            let focus_break =
+                self.focus.as_ref().is_some_and(|f| f.is_over(at)) && self.end_focus(at);
 
-                        self.tx.send(c).unwrap();
+            let pending = self.timers.all().len();
+            let timers = self.timers.take_due(now.naive_local());
+            let due: Vec<&Category> = Category::ALL
+                .iter()
+                .filter(|c| {
+                    timers.iter().any(|t| t.category == **c)
+                        || self
+                            .schedules
+                            .get(c)
+                            .is_some_and(|s| is_alarm_due(s, today, current_hour, current_minute))
+                })
+                .collect();
+
+            //timers are set on purpose, so a focus session doesn't hold
+            //them back
+            let break_timer = timers.iter().any(|t| t.category == Category::Break);
+
+            //a break played at the same time covers the micro-break
+            let break_played = focus_break
+                || (due.contains(&&Category::Break) && (self.focus.is_none() || break_timer));
+            let mut deferred = false;
+
+            for category in &due {
+                match self.focus.as_mut() {
+                    Some(focus) if **category == Category::Break && !break_timer => {
+                        debug!("alarm_manager: break deferred by focus session");
+                        focus.defer(at);
+                        self.stats.defer(at, category);
+                        deferred = true;
                     }
+                    _ if **category == Category::Break && focus_break => (),
+                    _ if **category == Category::Eyes && break_played => (),
+                    _ => {
+                        //None for timers, which come from no rule
+                        let rule = self
+                            .schedules
+                            .get(category)
+                            .and_then(|s| s.rule_at(today, current_hour, current_minute));
+                        self.play_alarm(category, at, self.break_seconds(category), rule)
+                    }
+                }
+            }
+
+            //heavy use of the keyboard or mouse brings a micro-break forward
+            let mut intense = false;
+            if let Some(monitor) = self.monitor.as_mut() {
+                let counts = monitor.take();
+                if self.intensity.add(at, counts, &self.intensity_settings)
+                    && !break_played
+                    && !due.contains(&&Category::Eyes)
+                {
+                    debug!("alarm_manager: input intensity reached");
+                    let seconds = self.break_seconds(&Category::Eyes);
+                    self.play_alarm(&Category::Eyes, at, seconds, None);
+                    intense = true;
                 }
             }
 
+            if !due.is_empty() || intense {
+                self.save_stats();
+            } else if active {
+                save(&get_stats_file_name(), &self.stats);
+            }
+            if self.timers.all().len() != pending {
+                self.save_timers();
+            }
+            if deferred {
+                self.save_focus();
+            }
+
             //notifying next alarm every minute helps to minimize
             //the time wrong value is displayed after waking up from sleep
             self.notify_next_alarm();
+            if self.pomodoro.is_running() {
+                self.notify_pomodoro();
+            }
+            if self.focus.is_some() {
+                self.notify_focus();
+            }
+        }
+
+        /// Plays an alarm of the category and tells the UI with an activity
+        /// lasting up to `seconds`. `rule` is the serial of the rule the
+        /// alarm comes from, if any
+        fn play_alarm(
+            &mut self,
+            category: &Category,
+            at: NaiveDateTime,
+            seconds: usize,
+            rule: Option<usize>,
+        ) {
+            debug!("alarm_manager: playing {:?} alarm", category);
+            let activity = self.pick_activity(category, seconds);
+            let reminder = self
+                .reminders
+                .get(category)
+                .cloned()
+                .unwrap_or_else(|| Reminder::default_for(category));
+            let sound = match reminder.sound {
+                Sound::Speech(message) => Sound::Speech(speech::fill(
+                    &message,
+                    rule,
+                    &reminder.text,
+                    activity.as_ref(),
+                    self.next_alarm(category, at).as_ref(),
+                )),
+                sound => sound,
+            };
+            //several categories may play in the same minute, each on its own
+            let audio = self.audio.clone();
+            tokio::task::spawn_blocking(move || play_sound(&sound, &audio));
+            match category {
+                Category::Break => {
+                    if let Some(alert) = self.alert.take() {
+                        debug!("alarm_manager: break at {} unanswered", alert.at);
+                        record_unanswered(&alert, &mut self.stats, &mut self.skipped);
+                        self.set_skipped();
+                    }
+                    self.alert = Some(Alert::new(at, Local::now().naive_local(), seconds));
+                }
+                Category::Eyes => {
+                    let audio = self.audio.clone();
+                    let seconds = seconds as u64;
+                    tokio::spawn(async move {
+                        tokio::time::sleep(Duration::from_secs(seconds)).await;
+                        //beeps block, so keep them off the runtime's workers
+                        tokio::task::spawn_blocking(move || play_chime(&audio));
+                    });
+                }
+                _ => (),
+            }
+            self.stats.record(at, category);
+
+            let c = Message {
+                typ: MessageType::EvtPlayingAlarm,
+                payload: Payload::Alarm(
+                    Alarm {
+                        day: at.weekday(),
+                        date: at.date(),
+                        hour: at.hour() as usize,
+                        min: at.minute() as usize,
+                        category: category.clone(),
+                    },
+                    activity,
+                    seconds,
+                ),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        /// Ends the focus session, making up for the breaks it held back
+        /// with a single longer one. Returns true if that was played
+        fn end_focus(&mut self, at: NaiveDateTime) -> bool {
+            let focus = match self.focus.take() {
+                Some(focus) => focus,
+                None => return false,
+            };
+
+            debug!(
+                "alarm_manager: focus session ended, {} breaks deferred",
+                focus.deferred.len()
+            );
+            let minutes = focus.break_minutes();
+            if let Some(minutes) = minutes {
+                self.play_alarm(&Category::Break, at, minutes as usize * 60, None);
+                self.save_stats();
+            }
+            self.save_focus();
+            minutes.is_some()
+        }
+
+        /// The current Pomodoro phase has run out
+        fn handle_phase_end(&mut self) {
+            if let Some(phase) = self.pomodoro.tick(&self.pomodoro_settings, Utc::now()) {
+                debug!("alarm_manager: pomodoro {:?}", phase);
+                let audio = self.audio.clone();
+                tokio::task::spawn_blocking(move || play_phase(&phase, &audio));
+                save(&get_pomodoro_file_name(), &self.pomodoro);
+            }
+
+            self.notify_pomodoro();
         }
 
         /// Handles message from ui_handlers
@@ -90,48 +376,527 @@ mod alarm_manager {
                 MessageType::CmdUpdateAlarms => {
                     self.update_alarms(msg.payload);
                     self.notify_next_alarm();
+                    self.notify_pomodoro();
+                    self.notify_stats();
+                    self.notify_timers();
+                    self.notify_focus();
+                }
+                MessageType::CmdPomodoroStart => {
+                    self.pomodoro.start(&self.pomodoro_settings, Utc::now());
+                    save(&get_pomodoro_file_name(), &self.pomodoro);
+                    self.notify_pomodoro();
+                }
+                MessageType::CmdPomodoroStop => {
+                    self.pomodoro.stop(Utc::now());
+                    save(&get_pomodoro_file_name(), &self.pomodoro);
+                    self.notify_pomodoro();
+                }
+                MessageType::CmdPomodoroReset => {
+                    self.pomodoro.reset();
+                    save(&get_pomodoro_file_name(), &self.pomodoro);
+                    self.notify_pomodoro();
+                }
+                MessageType::CmdAddTimer => {
+                    if let Payload::Timer(at, category) = msg.payload {
+                        let timer = self.timers.add(at, &category);
+                        debug!("alarm_manager: added timer {:?}", timer);
+                        self.save_timers();
+                        self.notify_next_alarm();
+                    }
+                }
+                MessageType::CmdCancelTimer => {
+                    if let Payload::TimerId(id) = msg.payload {
+                        if self.timers.cancel(id) {
+                            self.save_timers();
+                            self.notify_next_alarm();
+                        }
+                    }
+                }
+                MessageType::CmdFocusStart => {
+                    if let Payload::Minutes(minutes) = msg.payload {
+                        if self.focus.is_none() {
+                            self.focus = Some(Focus::new(Local::now().naive_local(), minutes));
+                            self.save_focus();
+                        }
+                    }
+                }
+                MessageType::CmdFocusExtend => {
+                    if let (Payload::Minutes(minutes), Some(focus)) = (msg.payload, &mut self.focus)
+                    {
+                        focus.extend(minutes);
+                        self.save_focus();
+                    }
+                }
+                MessageType::CmdFocusEnd => {
+                    let now = Local::now().naive_local();
+                    self.end_focus(now.with_second(0).unwrap_or(now));
+                }
+                MessageType::CmdBreakAcknowledge => self.answer_alert(false),
+                MessageType::CmdBreakSkip => self.answer_alert(true),
+                MessageType::CmdGetActiveTime => {
+                    if let Payload::Days(days) = msg.payload {
+                        let today = Local::now().date_naive();
+                        let c = Message {
+                            typ: MessageType::EvtActiveTime,
+                            payload: Payload::ActiveTime(
+                                self.stats.active_days(today, days),
+                                activity_readable(),
+                            ),
+                        };
+
+                        self.tx.send(c).unwrap();
+                    }
                 }
                 _ => debug!("alarm_manager::Unknown command"),
             }
         }
 
+        /// The earlier of the rules' next alarm and the next timer of the
+        /// category after `now`
+        fn next_alarm(&self, category: &Category, now: NaiveDateTime) -> Option<Alarm> {
+            let rules = self.schedules.get(category).and_then(|s| {
+                find_next_alarm(s, now.date(), now.hour() as usize, now.minute() as usize)
+            });
+            let timer = self.timers.next(category).map(Timer::alarm);
+
+            [rules, timer]
+                .into_iter()
+                .flatten()
+                .min_by_key(|a| (a.date, a.hour, a.min))
+        }
+
         fn notify_next_alarm(&self) {
             let now = Local::now();
-            let current_weekday: Weekday = now.weekday();
-            let current_hour: usize = now.hour() as usize;
-            let current_minute: usize = now.minute() as usize;
+            let next_alarms: Vec<Alarm> = Category::ALL
+                .iter()
+                .filter_map(|c| self.next_alarm(c, now.naive_local()))
+                .collect();
+
+            //all categories share the busy blocks
+            let busy = self
+                .schedules
+                .get(&Category::Break)
+                .map(|s| {
+                    s.busy_between(
+                        now.naive_local(),
+                        now.naive_local() + chrono::Duration::days(1),
+                    )
+                })
+                .unwrap_or_default();
 
             let c = Message {
                 typ: MessageType::EvtNextAlarm,
-                payload: Payload::Alarm(find_next_alarm(
-                    &self.alarms,
-                    current_weekday,
-                    current_hour,
-                    current_minute,
-                )),
+                payload: Payload::NextAlarm(next_alarms, busy),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        fn notify_pomodoro(&self) {
+            let status = self.pomodoro.status(&self.pomodoro_settings, Utc::now());
+
+            let c = Message {
+                typ: MessageType::EvtPomodoro,
+                payload: Payload::Pomodoro(status),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        fn notify_stats(&self) {
+            let c = Message {
+                typ: MessageType::EvtStats,
+                payload: Payload::Stats(self.stats.clone()),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        fn notify_timers(&self) {
+            let c = Message {
+                typ: MessageType::EvtTimers,
+                payload: Payload::Timers(self.timers.all().to_vec()),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        fn notify_focus(&self) {
+            let status = self
+                .focus
+                .as_ref()
+                .map(|f| f.status(Local::now().naive_local()))
+                .unwrap_or_default();
+
+            let c = Message {
+                typ: MessageType::EvtFocus,
+                payload: Payload::Focus(status),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        /// Tells the UI that a feature which is turned on can't work here
+        fn notify_unsupported(&self, feature: &str, reason: String) {
+            let c = Message {
+                typ: MessageType::EvtUnsupported,
+                payload: Payload::Unsupported(feature.to_string(), reason),
             };
 
             self.tx.send(c).unwrap();
         }
 
+        fn save_focus(&self) {
+            save(&get_focus_file_name(), &self.focus);
+            self.notify_focus();
+        }
+
+        fn save_timers(&self) {
+            save(&get_timers_file_name(), &self.timers);
+            self.notify_timers();
+        }
+
+        fn save_stats(&self) {
+            save(&get_stats_file_name(), &self.stats);
+            self.notify_stats();
+        }
+
         fn update_alarms(&mut self, payload: Payload) {
-            let rules;
-            if let Payload::Rules(temp) = payload {
-                rules = temp;
+            let settings;
+            if let Payload::Settings(temp) = payload {
+                settings = *temp;
             } else {
                 return;
             }
 
-            self.alarms = get_alarms(&rules);
+            self.schedules = schedules(&settings);
+            self.reminders = reminders(&settings);
+            self.audio = settings.audio;
+            self.activities = library(&settings.activities);
+            self.set_skipped();
+            self.meeting_files = settings.meetings.files;
+            self.pomodoro_settings = settings.pomodoro;
+            self.intensity_settings = settings.intensity;
+            self.daily_limit = settings.daily_limit;
+            if self.daily_limit > 0 && !activity_readable() {
+                self.notify_unsupported(
+                    "daily-limit",
+                    "Screen lock and input can't be read on this platform".to_string(),
+                );
+            }
+            if !self.intensity_settings.enabled {
+                self.monitor = None;
+            } else if self.monitor.is_none() {
+                match start_monitor() {
+                    Ok(monitor) => self.monitor = Some(monitor),
+                    Err(e) => {
+                        debug!("alarm_manager: no input monitor: {}", e);
+                        self.notify_unsupported("intensity", e);
+                    }
+                }
+            }
+            self.calendar_stamp = None;
+            self.refresh_busy_blocks();
+        }
+
+        /// Activity for an alarm of the category, remembered as shown
+        fn pick_activity(&mut self, category: &Category, seconds: usize) -> Option<Activity> {
+            let activity = pick(&self.activities, category, seconds, &self.shown)?.clone();
+
+            self.shown.retain(|t| *t != activity.title);
+            self.shown.push(activity.title.clone());
+            save(&get_shown_file_name(), &self.shown);
+            Some(activity)
+        }
+
+        fn break_seconds(&self, category: &Category) -> usize {
+            break_seconds(self.reminders.get(category), category)
+        }
+
+        /// Repeats the alert of an unanswered break a level up, or ends it
+        /// once the screen gets locked
+        fn escalate_alert(&mut self) {
+            //the lock state is only read while alerting
+            if self.alert.is_none() {
+                return;
+            }
+
+            let progress = advance_alert(
+                &mut self.alert,
+                Local::now().naive_local(),
+                &is_locked(),
+                &mut self.stats,
+                &mut self.skipped,
+            );
+            let alert = match (progress, self.alert.as_ref()) {
+                (Progress::Taken(response), _) => {
+                    debug!("alarm_manager: screen locked, break taken");
+                    self.save_stats();
+                    return self.notify_alert_ended(response);
+                }
+                (Progress::Escalated, Some(alert)) => alert,
+                _ => return,
+            };
+            debug!("alarm_manager: alert up to level {}", alert.level);
+            let (level, audio) = (alert.level, self.audio.clone());
+            tokio::task::spawn_blocking(move || play_escalation(level, MAX_LEVEL, &audio));
+
+            let c = Message {
+                typ: MessageType::EvtAlert,
+                payload: Payload::Alert(alert.clone()),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        /// The user is taking the break now, or skipping it
+        fn answer_alert(&mut self, skipped: bool) {
+            let alert = match self.alert.take() {
+                Some(alert) => alert,
+                None => return,
+            };
+
+            let response = record_answer(
+                &alert,
+                Local::now().naive_local(),
+                skipped,
+                &mut self.stats,
+                &mut self.skipped,
+            );
+            debug!("alarm_manager: break answered {:?}", response);
+            self.set_skipped();
+            self.save_stats();
+            self.notify_alert_ended(response);
+            if skipped {
+                self.notify_next_alarm();
+            }
+        }
+
+        fn notify_alert_ended(&self, response: Response) {
+            let c = Message {
+                typ: MessageType::EvtAlertEnded,
+                payload: Payload::Response(response),
+            };
+
+            self.tx.send(c).unwrap();
+        }
+
+        /// Counts the minute as one of use unless the screen is locked or
+        /// there was no input for IDLE_SECONDS. Nothing is counted when
+        /// neither can be read. Alerts once a day when the use reaches the
+        /// daily limit. Returns true if the minute was counted
+        fn track_active(&mut self, today: NaiveDate) -> bool {
+            let idle = idle_seconds();
+            let active = match is_locked() {
+                LockedState::Locked => false,
+                LockedState::Unlocked => !idle.is_some_and(|s| s >= IDLE_SECONDS),
+                LockedState::Unknown => idle.is_some_and(|s| s < IDLE_SECONDS),
+            };
+            if !active {
+                return false;
+            }
+
+            let minutes = self.stats.add_active(today);
+            if self.daily_limit > 0
+                && minutes >= self.daily_limit
+                && self.limit_reached != Some(today)
+            {
+                debug!(
+                    "alarm_manager: daily limit reached after {} minutes",
+                    minutes
+                );
+                self.limit_reached = Some(today);
+                let audio = self.audio.clone();
+                tokio::task::spawn_blocking(move || play_limit(&audio));
+
+                let c = Message {
+                    typ: MessageType::EvtDailyLimit,
+                    payload: Payload::DailyLimit(minutes, self.daily_limit),
+                };
+
+                self.tx.send(c).unwrap();
+            }
+
+            true
+        }
+
+        /// Skipped breaks only shorten the intervals of ramped break rules
+        fn set_skipped(&mut self) {
+            if let Some(s) = self.schedules.get_mut(&Category::Break) {
+                s.set_skipped(self.skipped.clone());
+            }
+        }
+
+        /// Re-reads meeting calendars when the files or the date change
+        fn refresh_busy_blocks(&mut self) {
+            let today = Local::now().date_naive();
+            let stamp = (today, modified_times(&self.meeting_files));
+            if self.calendar_stamp.as_ref() == Some(&stamp) {
+                return;
+            }
+
+            let from = today.and_time(NaiveTime::MIN);
+            let to = from + chrono::Duration::days(BUSY_WINDOW_DAYS);
+            let busy = read_busy_blocks(&self.meeting_files, &Local, from, to);
+            for s in self.schedules.values_mut() {
+                s.set_busy(busy.clone());
+            }
+            self.calendar_stamp = Some(stamp);
+        }
+    }
+
+    /// Length of an alarm's break in seconds, as set in the category's
+    /// reminder or else the category's default
+    fn break_seconds(reminder: Option<&Reminder>, category: &Category) -> usize {
+        reminder.and_then(|r| r.duration).unwrap_or(match category {
+            Category::Break => BREAK_MINUTES as usize * 60,
+            Category::Eyes => MICRO_BREAK_SECONDS,
+            Category::Stretch => STRETCH_SECONDS,
+            Category::Water | Category::Custom => SHORT_BREAK_SECONDS,
+        })
+    }
+
+    /// What became of an unanswered alert at a minute tick
+    #[derive(Debug, PartialEq)]
+    enum Progress {
+        Pending,
+        Escalated,
+        //the screen got locked, answering the alert
+        Taken(Response),
+    }
+
+    /// Ends the alert once the screen gets locked, which counts as taking
+    /// the break, or else moves it up a level when due. Whether the break
+    /// was skipped is left to the answer, so that a break is counted once
+    /// however long it goes unanswered
+    fn advance_alert(
+        alert: &mut Option<Alert>,
+        now: NaiveDateTime,
+        locked: &LockedState,
+        stats: &mut Stats,
+        skipped_breaks: &mut Vec<NaiveDateTime>,
+    ) -> Progress {
+        if matches!(locked, LockedState::Locked) {
+            if let Some(taken) = alert.take() {
+                return Progress::Taken(record_answer(&taken, now, false, stats, skipped_breaks));
+            }
+        }
+
+        if alert.as_mut().is_some_and(|a| a.escalate(now)) {
+            Progress::Escalated
+        } else {
+            Progress::Pending
+        }
+    }
+
+    /// Records the answer to a break's alert. A skipped break counts towards
+    /// the stats and the ramped rules
+    fn record_answer(
+        alert: &Alert,
+        now: NaiveDateTime,
+        skipped: bool,
+        stats: &mut Stats,
+        skipped_breaks: &mut Vec<NaiveDateTime>,
+    ) -> Response {
+        let response = alert.respond(now, skipped);
+        if skipped {
+            skipped_breaks.push(alert.at);
+        }
+        stats.respond(alert.at, &Category::Break, response.seconds, skipped);
+        response
+    }
+
+    /// Records a break whose alert was replaced by the next one before it
+    /// was answered as skipped, without a response time
+    fn record_unanswered(
+        alert: &Alert,
+        stats: &mut Stats,
+        skipped_breaks: &mut Vec<NaiveDateTime>,
+    ) {
+        skipped_breaks.push(alert.at);
+        stats.skip(alert.at, &Category::Break);
+    }
+
+    /// Whether the use can be tracked at all, i.e. the screen lock or the
+    /// time since the last input can be read
+    fn activity_readable() -> bool {
+        idle_seconds().is_some() || !matches!(is_locked(), LockedState::Unknown)
+    }
+
+    fn schedules(settings: &Settings) -> HashMap<Category, Schedule> {
+        Category::ALL
+            .iter()
+            .map(|c| (c.clone(), Schedule::for_category(settings, c)))
+            .collect()
+    }
+
+    fn reminders(settings: &Settings) -> HashMap<Category, Reminder> {
+        Category::ALL
+            .iter()
+            .map(|c| (c.clone(), settings.reminder(c)))
+            .collect()
+    }
+
+    /// State saved by the previous run, if any
+    fn load<T: DeserializeOwned + Default>(path: &str) -> T {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save<T: Serialize>(path: &str, value: &T) {
+        let result = serde_json::to_string(value)
+            .map_err(|e| e.to_string())
+            .and_then(|s| std::fs::write(path, s).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            debug!("alarm_manager: unable to save {}: {}", path, e);
         }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::ui_handler::{
+            Anchor, Category, DateOverride, DateRange, Exceptions, MeetingAction, Ramp, Random,
+            Recurrence, Rule, RuleKind, Settings, Sound, TimeWindow, Tone,
+        };
         use crate::utils::*;
-        use crate::Rule;
+        use chrono::{NaiveDate, NaiveTime, Weekday};
         use maplit::hashmap;
+        use proptest::prelude::*;
+        use std::collections::HashMap;
+
+        fn schedule(rules: Vec<Rule>) -> Schedule {
+            Schedule::for_category(
+                &Settings {
+                    rules,
+                    ..Default::default()
+                },
+                &Category::Break,
+            )
+        }
+
+        /// Date of the given weekday in the week starting Mon 5 Jan 2026
+        fn date(day: Weekday) -> NaiveDate {
+            NaiveDate::from_ymd_opt(2026, 1, 5).unwrap()
+                + chrono::Duration::days(day.num_days_from_monday() as i64)
+        }
+
+        fn next_week(day: Weekday) -> NaiveDate {
+            date(day) + chrono::Duration::days(7)
+        }
+
+        fn alarm(date: NaiveDate, hour: usize, min: usize) -> Option<Alarm> {
+            Some(Alarm {
+                day: date.weekday(),
+                date,
+                hour,
+                min,
+                category: Category::Break,
+            })
+        }
 
         #[test]
         fn test_get_alarms() {
@@ -142,6 +907,7 @@ mod alarm_manager {
                 interval: 1,
                 from: 18,
                 to: 19,
+                ..Default::default()
             };
 
             let rule2 = Rule {
@@ -150,6 +916,7 @@ mod alarm_manager {
                 interval: 30,
                 from: 19,
                 to: 20,
+                ..Default::default()
             };
 
             let rules = vec![rule1, rule2];
@@ -174,98 +941,846 @@ mod alarm_manager {
             assert_eq!(alarms, expected);
         }
 
+        /// Brute force version of get_alarms. Checks every minute of the day
+        fn reference_alarms(rules: &[Rule]) -> HashMap<Weekday, HashMap<usize, Vec<usize>>> {
+            let mut alarms: HashMap<Weekday, HashMap<usize, Vec<usize>>> = HashMap::new();
+
+            for r in rules {
+                for d in &r.days {
+                    let hours = alarms.entry(get_weekday(d).unwrap()).or_default();
+
+                    let start = (r.from * 60 + r.anchor.offset) as i64;
+                    let end = (r.to * 60 + r.anchor.offset) as i64;
+
+                    for minute in 0..24 * 60 {
+                        let since_start = minute as i64 - start;
+                        let after_start =
+                            since_start > 0 || (since_start == 0 && r.anchor.include_start);
+                        let before_end =
+                            (minute as i64) < end || (minute as i64 == end && r.anchor.include_end);
+
+                        if after_start && before_end && since_start % r.interval as i64 == 0 {
+                            let mins = hours.entry(minute / 60).or_default();
+                            if !mins.contains(&(minute % 60)) {
+                                mins.push(minute % 60);
+                            }
+                        }
+                    }
+                }
+            }
+
+            for hours in alarms.values_mut() {
+                for mins in hours.values_mut() {
+                    mins.sort();
+                }
+            }
+
+            alarms
+        }
+
         #[test]
-        fn test_next_1() {
-            setup_logger();
-            let rule1 = Rule {
+        fn test_get_alarms_long_intervals() {
+            let rule = |interval, from, to| Rule {
                 serial: 1,
-                days: vec!["Fri".to_string()],
-                interval: 30,
-                from: 17,
-                to: 18,
+                days: vec!["Mon".to_string()],
+                interval,
+                from,
+                to,
+                ..Default::default()
             };
 
-            let rule2 = Rule {
-                serial: 2,
-                days: vec!["Sun".to_string()],
+            let cases = vec![
+                (
+                    rule(45, 9, 12),
+                    hashmap! {
+                        9 => vec![45],
+                        10 => vec![30],
+                        11 => vec![15],
+                        12 => vec![0],
+                    },
+                ),
+                (
+                    rule(90, 9, 13),
+                    hashmap! {
+                        10 => vec![30],
+                        12 => vec![0],
+                    },
+                ),
+                (
+                    rule(120, 8, 17),
+                    hashmap! {
+                        10 => vec![0],
+                        12 => vec![0],
+                        14 => vec![0],
+                        16 => vec![0],
+                    },
+                ),
+                (rule(60, 9, 11), hashmap! { 10 => vec![0], 11 => vec![0] }),
+                (rule(25, 9, 10), hashmap! { 9 => vec![25, 50] }),
+                (rule(90, 9, 10), hashmap! {}),
+            ];
+
+            for (r, expected) in cases {
+                let interval = r.interval;
+                let alarms = get_alarms(&[r]);
+                assert_eq!(
+                    alarms.get(&Weekday::Mon).cloned().unwrap_or_default(),
+                    expected,
+                    "interval {}",
+                    interval
+                );
+            }
+        }
+
+        #[test]
+        fn test_get_alarms_anchor() {
+            let rule = |include_start, include_end, offset| Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
                 interval: 30,
-                from: 19,
-                to: 20,
+                from: 9,
+                to: 10,
+                anchor: Anchor {
+                    include_start,
+                    include_end,
+                    offset,
+                },
+                ..Default::default()
             };
 
-            let rules = vec![rule1, rule2];
-            let alarms = get_alarms(&rules);
+            let cases = vec![
+                (
+                    rule(false, true, 0),
+                    hashmap! { 9 => vec![30], 10 => vec![0] },
+                ),
+                (
+                    rule(true, true, 0),
+                    hashmap! { 9 => vec![0, 30], 10 => vec![0] },
+                ),
+                (rule(false, false, 0), hashmap! { 9 => vec![30] }),
+                (rule(true, false, 0), hashmap! { 9 => vec![0, 30] }),
+                (
+                    rule(false, true, 5),
+                    hashmap! { 9 => vec![35], 10 => vec![5] },
+                ),
+                (rule(true, false, 5), hashmap! { 9 => vec![5, 35] }),
+            ];
 
-            let expected = hashmap! {
-                Weekday::Fri => hashmap! {
-                    17 => vec![30],
-                    18 => vec![0],
-                },
-                // Add more weekdays and alarms as needed
-                Weekday::Sun => hashmap! {
-                    19 => vec![30],
-                    20 => vec![0],
+            for (r, expected) in cases {
+                let anchor = r.anchor.clone();
+                let alarms = get_alarms(&[r]);
+                assert_eq!(
+                    alarms.get(&Weekday::Mon).cloned().unwrap_or_default(),
+                    expected,
+                    "{:?}",
+                    anchor
+                );
+            }
+        }
+
+        #[test]
+        fn test_next_with_anchor() {
+            let rule = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Tue".to_string()],
+                interval: 60,
+                from: 9,
+                to: 12,
+                anchor: Anchor {
+                    include_start: true,
+                    include_end: false,
+                    offset: 5,
                 },
+                ..Default::default()
             };
+            let schedule = schedule(vec![rule]);
 
-            assert_eq!(alarms, expected);
+            let next = |day, h, m| find_next_alarm(&schedule, date(day), h, m);
+
+            assert_eq!(next(Weekday::Mon, 8, 0), alarm(date(Weekday::Mon), 9, 5));
+            assert_eq!(next(Weekday::Mon, 9, 5), alarm(date(Weekday::Mon), 10, 5));
+            //12:05 is the end of the window and excluded
+            assert_eq!(next(Weekday::Mon, 11, 5), alarm(date(Weekday::Tue), 9, 5));
+        }
+
+        #[test]
+        fn test_next_random() {
+            let rule = Rule {
+                serial: 7,
+                days: vec!["Mon".to_string()],
+                from: 9,
+                to: 17,
+                random: Some(Random::Breaks {
+                    count: 3,
+                    min_gap: 90,
+                }),
+                ..Default::default()
+            };
+            let settings = Settings {
+                rules: vec![rule],
+                ..Default::default()
+            };
+
+            let schedule = Schedule::for_category(&settings, &Category::Break);
+            let alarms = schedule.alarms_on(date(Weekday::Mon));
+            let mut times: Vec<(usize, usize)> = alarms
+                .iter()
+                .flat_map(|(h, mins)| mins.iter().map(move |m| (*h, *m)))
+                .collect();
+            times.sort();
+            assert_eq!(times.len(), 3);
 
+            //a restart gives the same alarms
+            let restarted = Schedule::for_category(&settings, &Category::Break);
+            assert_eq!(restarted.alarms_on(date(Weekday::Mon)), alarms);
+
+            let (hour, min) = times[0];
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Fri, 17, 58),
-                Some(Alarm {
-                    day: Weekday::Fri,
-                    hour: 18,
-                    min: 0
-                })
+                find_next_alarm(&restarted, date(Weekday::Mon), 8, 0),
+                alarm(date(Weekday::Mon), hour, min)
             );
+        }
+
+        #[test]
+        fn test_min_gap() {
+            let rule = |serial, interval, from, to, priority| Rule {
+                serial,
+                days: vec!["Mon".to_string()],
+                interval,
+                from,
+                to,
+                priority,
+                ..Default::default()
+            };
+
+            let mut settings = Settings {
+                rules: vec![rule(1, 30, 9, 10, 0), rule(2, 20, 9, 10, 0)],
+                ..Default::default()
+            };
+
+            //without a gap, only alarms at the same minute are merged
+            let (alarms, merges) =
+                Schedule::for_category(&settings, &Category::Break).preview_on(date(Weekday::Mon));
+            assert_eq!(alarms, hashmap! { 9 => vec![20, 30, 40], 10 => vec![0] });
+            assert!(merges.is_empty());
+
+            //on equal priority the earlier alarm wins
+            settings.min_gap = 15;
+            let (alarms, merges) =
+                Schedule::for_category(&settings, &Category::Break).preview_on(date(Weekday::Mon));
+            assert_eq!(alarms, hashmap! { 9 => vec![20, 40], 10 => vec![0] });
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sat, 18, 0),
-                Some(Alarm {
-                    day: Weekday::Sun,
-                    hour: 19,
-                    min: 30
-                })
+                merges
+                    .iter()
+                    .map(|m| (m.hour, m.min, m.into_hour, m.into_min))
+                    .collect::<Vec<_>>(),
+                vec![(9, 30, 9, 20), (10, 0, 10, 0)]
             );
+
+            settings.rules[0].priority = 1;
+            let schedule = Schedule::for_category(&settings, &Category::Break);
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sun, 18, 0),
-                Some(Alarm {
-                    day: Weekday::Sun,
-                    hour: 19,
-                    min: 30
-                })
+                schedule.alarms_on(date(Weekday::Mon)),
+                hashmap! { 9 => vec![30], 10 => vec![0] }
             );
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sun, 19, 0),
-                Some(Alarm {
-                    day: Weekday::Sun,
-                    hour: 19,
-                    min: 30
-                })
+                find_next_alarm(&schedule, date(Weekday::Mon), 9, 0),
+                alarm(date(Weekday::Mon), 9, 30)
             );
+            //the rule which won is the one the alarm comes from
+            assert_eq!(schedule.rule_at(date(Weekday::Mon), 9, 30), Some(1));
+            assert_eq!(schedule.rule_at(date(Weekday::Mon), 9, 20), None);
+        }
+
+        #[test]
+        fn test_min_gap_past_midnight() {
+            let cron = |serial, expression: &str| Rule {
+                serial,
+                kind: RuleKind::Cron,
+                expression: Some(expression.to_string()),
+                ..Default::default()
+            };
+
+            let settings = Settings {
+                rules: vec![cron(1, "55 23 * * *"), cron(2, "5 0,9 * * *")],
+                min_gap: 15,
+                ..Default::default()
+            };
+
+            //00:05 is too close to 23:55 the evening before
+            let (alarms, merges) =
+                Schedule::for_category(&settings, &Category::Break).preview_on(date(Weekday::Tue));
+            assert_eq!(alarms, hashmap! { 9 => vec![5], 23 => vec![55] });
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sun, 19, 20),
-                Some(Alarm {
-                    day: Weekday::Sun,
-                    hour: 19,
-                    min: 30
-                })
+                merges
+                    .iter()
+                    .map(|m| (
+                        m.hour,
+                        m.min,
+                        m.serial,
+                        m.into_hour,
+                        m.into_min,
+                        m.into_serial
+                    ))
+                    .collect::<Vec<_>>(),
+                vec![(0, 5, 2, 23, 55, 1)]
             );
+        }
+
+        fn window(from: &str, to: &str) -> TimeWindow {
+            TimeWindow {
+                from: NaiveTime::parse_from_str(from, "%H:%M").unwrap(),
+                to: NaiveTime::parse_from_str(to, "%H:%M").unwrap(),
+            }
+        }
+
+        #[test]
+        fn test_exclusions() {
+            let rule = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
+                interval: 30,
+                from: 9,
+                to: 18,
+                exclusions: vec![window("12:30", "13:30"), window("10:00", "10:15")],
+                ..Default::default()
+            };
+
+            let alarms = get_alarms(std::slice::from_ref(&rule));
+            let expected = hashmap! {
+                9 => vec![30],
+                10 => vec![30],
+                11 => vec![0, 30],
+                12 => vec![0],
+                13 => vec![30],
+                14 => vec![0, 30],
+                15 => vec![0, 30],
+                16 => vec![0, 30],
+                17 => vec![0, 30],
+                18 => vec![0],
+            };
+            assert_eq!(alarms[&Weekday::Mon], expected);
+            assert_eq!(schedule(vec![rule]).alarms_on(date(Weekday::Mon)), expected);
+        }
+
+        #[test]
+        fn test_quiet_hours() {
+            let rule = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Tue".to_string()],
+                interval: 60,
+                from: 5,
+                to: 23,
+                ..Default::default()
+            };
+
+            let schedule = Schedule::for_category(
+                &Settings {
+                    rules: vec![rule],
+                    quiet_hours: vec![window("22:00", "07:00")],
+                    ..Default::default()
+                },
+                &Category::Break,
+            );
+
+            let alarms = schedule.alarms_on(date(Weekday::Mon));
+            let mut hours: Vec<usize> = alarms.keys().cloned().collect();
+            hours.sort();
+            assert_eq!(hours, (7..22).collect::<Vec<usize>>());
+
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sun, 19, 31),
+                find_next_alarm(&schedule, date(Weekday::Mon), 21, 0),
+                alarm(date(Weekday::Tue), 7, 0)
+            );
+        }
+
+        #[test]
+        fn test_ramp_after_skips() {
+            let rule = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Tue".to_string()],
+                interval: 60,
+                from: 9,
+                to: 17,
+                ramp: Some(Ramp {
+                    per_skip: 20,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+
+            let mut schedule = schedule(vec![rule]);
+            let monday = date(Weekday::Mon);
+            assert_eq!(
+                find_next_alarm(&schedule, monday, 10, 5),
+                alarm(monday, 11, 0)
+            );
+
+            //skips only shorten the interval on the day they happen
+            schedule.set_skipped(vec![monday.and_hms_opt(10, 0, 0).unwrap()]);
+            assert_eq!(
+                find_next_alarm(&schedule, monday, 10, 5),
+                alarm(monday, 10, 40)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Tue), 10, 5),
+                alarm(date(Weekday::Tue), 11, 0)
+            );
+        }
+
+        #[test]
+        fn test_micro_breaks() {
+            let breaks = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
+                interval: 60,
+                from: 9,
+                to: 11,
+                ..Default::default()
+            };
+
+            let eyes = Rule {
+                serial: 2,
+                interval: 20,
+                category: Category::Eyes,
+                ..breaks.clone()
+            };
+
+            let settings = Settings {
+                rules: vec![breaks, eyes],
+                min_gap: 30,
+                ..Default::default()
+            };
+
+            //the categories are scheduled apart, so the gap doesn't merge
+            //micro-breaks into the breaks
+            let monday = date(Weekday::Mon);
+            assert_eq!(
+                Schedule::for_category(&settings, &Category::Break).alarms_on(monday),
+                hashmap! { 10 => vec![0], 11 => vec![0] }
+            );
+            assert_eq!(
+                Schedule::for_category(&settings, &Category::Eyes).alarms_on(monday),
+                hashmap! { 9 => vec![20, 40], 10 => vec![0, 20, 40], 11 => vec![0] }
+            );
+        }
+
+        #[test]
+        fn test_next_per_category() {
+            let water = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
+                interval: 90,
+                from: 9,
+                to: 12,
+                category: Category::Water,
+                ..Default::default()
+            };
+
+            let settings = Settings {
+                rules: vec![water, workday_rule()],
+                ..Default::default()
+            };
+
+            let monday = date(Weekday::Mon);
+            let next = find_next_alarm(
+                &Schedule::for_category(&settings, &Category::Water),
+                monday,
+                9,
+                0,
+            );
+            assert_eq!(
+                next,
                 Some(Alarm {
-                    day: Weekday::Sun,
-                    hour: 20,
-                    min: 0
+                    category: Category::Water,
+                    ..alarm(monday, 10, 30).unwrap()
                 })
             );
+
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sun, 20, 31),
-                Some(Alarm {
-                    day: Weekday::Fri,
-                    hour: 17,
-                    min: 30
+                find_next_alarm(
+                    &Schedule::for_category(&settings, &Category::Break),
+                    monday,
+                    9,
+                    0
+                ),
+                alarm(monday, 9, 30)
+            );
+            assert_eq!(
+                find_next_alarm(
+                    &Schedule::for_category(&settings, &Category::Stretch),
+                    monday,
+                    9,
+                    0
+                ),
+                None
+            );
+
+            //categories not configured get their default sound and text
+            assert_eq!(settings.reminder(&Category::Eyes).sound, Sound::Soft);
+        }
+
+        #[test]
+        fn test_tone_reminders() {
+            let settings: Settings = serde_json::from_value(serde_json::json!({
+                "rules": [],
+                "reminders": {
+                    "water": {
+                        "sound": {"tones": [
+                            {"frequency": 880, "duration": 150, "gap": 50},
+                            {"frequency": 660, "duration": 300}
+                        ]},
+                        "text": "Drink some water"
+                    },
+                    "stretch": {"sound": "double", "text": "Stretch"},
+                    "eyes": {"sound": {"tones": [{"frequency": 0, "duration": 150}]}, "text": "Look"},
+                    "custom": {"sound": {"tones": [{"frequency": 440, "duration": 600000}]}, "text": "Hum"},
+                    "break": {"sound": {"tones": vec![serde_json::json!({"frequency": 440, "duration": 10000}); 4]}, "text": "Hum"}
+                }
+            }))
+            .unwrap();
+
+            let reminders = reminders(&settings);
+            //unplayable tones fall back to the default
+            assert_eq!(reminders[&Category::Eyes].sound, Sound::Soft);
+            assert_eq!(reminders[&Category::Custom].sound, Sound::Beep);
+            assert_eq!(
+                reminders[&Category::Water].sound,
+                Sound::Tones(vec![
+                    Tone {
+                        frequency: 880,
+                        duration: 150,
+                        gap: 50
+                    },
+                    Tone {
+                        frequency: 660,
+                        duration: 300,
+                        gap: 0
+                    }
+                ])
+            );
+            assert_eq!(reminders[&Category::Stretch].sound, Sound::Double);
+            //each tone is short enough but the pattern is not
+            assert_eq!(reminders[&Category::Break].sound, Sound::Beeps);
+        }
+
+        #[test]
+        fn test_break_seconds() {
+            let settings: Settings = serde_json::from_value(serde_json::json!({
+                "rules": [],
+                "reminders": {
+                    "water": {"sound": "double", "text": "Drink", "duration": 45}
+                }
+            }))
+            .unwrap();
+
+            let reminders = reminders(&settings);
+            let seconds = |c: Category| break_seconds(reminders.get(&c), &c);
+            assert_eq!(seconds(Category::Water), 45);
+            assert_eq!(seconds(Category::Break), BREAK_MINUTES as usize * 60);
+            assert_eq!(seconds(Category::Eyes), MICRO_BREAK_SECONDS);
+            assert_eq!(seconds(Category::Stretch), STRETCH_SECONDS);
+        }
+
+        #[test]
+        fn test_unanswered_then_skipped() {
+            let at = date(Weekday::Mon).and_hms_opt(10, 0, 0).unwrap();
+            let mut stats = Stats::default();
+            let mut skipped = Vec::new();
+            stats.record(at, &Category::Break);
+            let mut alert = Some(Alert::new(at, at, 300));
+
+            //left unanswered well past the break's length
+            for minute in 1..=15 {
+                let now = at + chrono::Duration::minutes(minute);
+                advance_alert(
+                    &mut alert,
+                    now,
+                    &LockedState::Unlocked,
+                    &mut stats,
+                    &mut skipped,
+                );
+            }
+            let unanswered = alert.take().unwrap();
+            assert_eq!(unanswered.level, MAX_LEVEL);
+            assert_eq!(stats.days[&at.date()][&Category::Break].skipped, 0);
+
+            let now = at + chrono::Duration::minutes(16);
+            let response = record_answer(&unanswered, now, true, &mut stats, &mut skipped);
+            assert_eq!(response.seconds, 16 * 60);
+            assert_eq!(skipped, vec![at]);
+            assert_eq!(stats.days[&at.date()][&Category::Break].skipped, 1);
+            assert_eq!(stats.history[0].response, Some(16 * 60));
+
+            //the next break replaces one never answered
+            let next = at + chrono::Duration::minutes(30);
+            stats.record(next, &Category::Break);
+            record_unanswered(&Alert::new(next, next, 300), &mut stats, &mut skipped);
+            assert_eq!(skipped, vec![at, next]);
+            assert_eq!(stats.days[&at.date()][&Category::Break].skipped, 2);
+            assert_eq!(stats.history[1].response, None);
+
+            //locking the screen takes the break, answering the alert
+            let last = at + chrono::Duration::minutes(60);
+            stats.record(last, &Category::Break);
+            let mut alert = Some(Alert::new(last, last, 300));
+            let now = last + chrono::Duration::minutes(2);
+            assert_eq!(
+                advance_alert(
+                    &mut alert,
+                    now,
+                    &LockedState::Locked,
+                    &mut stats,
+                    &mut skipped
+                ),
+                Progress::Taken(Response {
+                    at: last,
+                    seconds: 120,
+                    skipped: false
                 })
             );
+            assert!(alert.is_none());
+            assert_eq!(stats.history[2].response, Some(120));
+            assert_eq!(skipped, vec![at, next]);
+            assert_eq!(stats.days[&at.date()][&Category::Break].skipped, 2);
+        }
+
+        fn jan(d: u32) -> NaiveDate {
+            NaiveDate::from_ymd_opt(2026, 1, d).unwrap()
+        }
+
+        fn workday_rule() -> Rule {
+            Rule {
+                serial: 1,
+                days: ["Mon", "Tue", "Wed", "Thu", "Fri"]
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect(),
+                interval: 30,
+                from: 9,
+                to: 10,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_validity_period() {
+            let rule = Rule {
+                valid_from: Some(jan(7)),
+                valid_until: Some(jan(20)),
+                ..workday_rule()
+            };
+            let schedule = schedule(vec![rule.clone()]);
+
+            assert!(schedule.alarms_on(jan(6)).is_empty());
+            assert!(!schedule.alarms_on(jan(20)).is_empty());
+            assert!(schedule.alarms_on(jan(21)).is_empty());
+            assert_eq!(
+                find_next_alarm(&schedule, jan(5), 8, 0),
+                alarm(jan(7), 9, 30)
+            );
+            assert_eq!(find_next_alarm(&schedule, jan(20), 10, 0), None);
+
+            let backwards = Rule {
+                valid_from: Some(jan(20)),
+                valid_until: Some(jan(7)),
+                ..rule
+            };
+            assert!(validate_rule(&backwards).is_err());
+
+            let overnight = Rule {
+                from: 22,
+                to: 2,
+                ..workday_rule()
+            };
+            assert!(validate_rule(&overnight).is_err());
+            let past_midnight = Rule {
+                to: 25,
+                ..workday_rule()
+            };
+            assert!(validate_rule(&past_midnight).is_err());
+            let whole_day = Rule {
+                from: 0,
+                to: 24,
+                ..workday_rule()
+            };
+            assert!(validate_rule(&whole_day).is_ok());
+        }
+
+        #[test]
+        fn test_every_other_week() {
+            let rule = Rule {
+                days: vec!["Mon".to_string()],
+                recurrence: Recurrence::Weeks {
+                    weeks: 2,
+                    start: jan(7),
+                },
+                ..workday_rule()
+            };
+            let schedule = schedule(vec![rule.clone()]);
+
+            assert!(!schedule.alarms_on(jan(5)).is_empty());
+            assert!(schedule.alarms_on(jan(12)).is_empty());
+            assert!(!schedule.alarms_on(jan(19)).is_empty());
+            assert!(schedule
+                .alarms_on(NaiveDate::from_ymd_opt(2025, 12, 29).unwrap())
+                .is_empty());
+
+            //more than a week ahead
+            assert_eq!(
+                find_next_alarm(&schedule, jan(5), 10, 0),
+                alarm(jan(19), 9, 30)
+            );
+
+            let never = Rule {
+                recurrence: Recurrence::Weeks {
+                    weeks: 0,
+                    start: jan(5),
+                },
+                ..rule
+            };
+            assert!(validate_rule(&never).is_err());
+        }
+
+        #[test]
+        fn test_nth_weekday_of_month() {
+            let rule = Rule {
+                days: vec!["Fri".to_string()],
+                recurrence: Recurrence::Month { weeks: vec![1, -1] },
+                ..workday_rule()
+            };
+            let schedule = schedule(vec![rule.clone()]);
+
+            //Fridays in January 2026 are the 2nd, 9th, 16th, 23rd and 30th
+            let fridays: Vec<u32> = [2, 9, 16, 23, 30]
+                .into_iter()
+                .filter(|d| !schedule.alarms_on(jan(*d)).is_empty())
+                .collect();
+            assert_eq!(fridays, vec![2, 30]);
+
+            assert_eq!(
+                find_next_alarm(&schedule, jan(3), 8, 0),
+                alarm(jan(30), 9, 30)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, jan(30), 10, 0),
+                alarm(NaiveDate::from_ymd_opt(2026, 2, 6).unwrap(), 9, 30)
+            );
+
+            let sixth = Rule {
+                recurrence: Recurrence::Month { weeks: vec![6] },
+                ..rule
+            };
+            assert!(validate_rule(&sixth).is_err());
+        }
+
+        fn arb_rule() -> impl Strategy<Value = Rule> {
+            let days = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+            (
+                proptest::sample::subsequence(days.to_vec(), 1..=7),
+                1usize..=300,
+                0usize..24,
+                0usize..24,
+                (any::<bool>(), any::<bool>(), 0usize..60),
+            )
+                .prop_map(
+                    |(days, interval, from, to, (include_start, include_end, offset))| Rule {
+                        serial: 1,
+                        days: days.iter().map(|d| d.to_string()).collect(),
+                        interval,
+                        from,
+                        to,
+                        anchor: Anchor {
+                            include_start,
+                            include_end,
+                            offset,
+                        },
+                        ..Default::default()
+                    },
+                )
+        }
+
+        proptest! {
+            #[test]
+            fn prop_get_alarms_matches_reference(rules in proptest::collection::vec(arb_rule(), 1..4)) {
+                let mut alarms = get_alarms(&rules);
+                //days whose rules produce no alarms may have empty entries
+                alarms.retain(|_, hours| !hours.is_empty());
+                let mut expected = reference_alarms(&rules);
+                expected.retain(|_, hours| !hours.is_empty());
+
+                prop_assert_eq!(alarms, expected);
+            }
+        }
+
+        #[test]
+        fn test_next_1() {
+            setup_logger();
+            let rule1 = Rule {
+                serial: 1,
+                days: vec!["Fri".to_string()],
+                interval: 30,
+                from: 17,
+                to: 18,
+                ..Default::default()
+            };
+
+            let rule2 = Rule {
+                serial: 2,
+                days: vec!["Sun".to_string()],
+                interval: 30,
+                from: 19,
+                to: 20,
+                ..Default::default()
+            };
+
+            let rules = vec![rule1, rule2];
+            let alarms = get_alarms(&rules);
+
+            let expected = hashmap! {
+                Weekday::Fri => hashmap! {
+                    17 => vec![30],
+                    18 => vec![0],
+                },
+                // Add more weekdays and alarms as needed
+                Weekday::Sun => hashmap! {
+                    19 => vec![30],
+                    20 => vec![0],
+                },
+            };
+
+            assert_eq!(alarms, expected);
+
+            let schedule = schedule(rules);
+
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Fri), 17, 58),
+                alarm(date(Weekday::Fri), 18, 0)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Sat), 18, 0),
+                alarm(date(Weekday::Sun), 19, 30)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Sun), 18, 0),
+                alarm(date(Weekday::Sun), 19, 30)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Sun), 19, 0),
+                alarm(date(Weekday::Sun), 19, 30)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Sun), 19, 20),
+                alarm(date(Weekday::Sun), 19, 30)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Sun), 19, 31),
+                alarm(date(Weekday::Sun), 20, 0)
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Sun), 20, 31),
+                alarm(next_week(Weekday::Fri), 17, 30)
+            );
         }
 
         #[test]
@@ -277,6 +1792,7 @@ mod alarm_manager {
                 interval: 2,
                 from: 12,
                 to: 13,
+                ..Default::default()
             };
 
             let rules = vec![rule1];
@@ -292,16 +1808,247 @@ mod alarm_manager {
 
             assert_eq!(alarms, expected);
 
+            let schedule = schedule(rules);
+
             assert_eq!(
-                find_next_alarm(&alarms, Weekday::Sat, 13, 12),
-                Some(Alarm {
-                    day: Weekday::Sat,
-                    hour: 12,
-                    min: 2
-                })
+                find_next_alarm(&schedule, date(Weekday::Sat), 13, 12),
+                alarm(next_week(Weekday::Sat), 12, 2)
+            );
+        }
+
+        #[test]
+        fn test_next_skips_exceptions() {
+            let rule1 = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Tue".to_string(), "Wed".to_string()],
+                interval: 30,
+                from: 9,
+                to: 10,
+                ..Default::default()
+            };
+
+            let settings = Settings {
+                rules: vec![rule1],
+                exceptions: Exceptions {
+                    dates: vec![date(Weekday::Tue)],
+                    ranges: vec![DateRange {
+                        from: date(Weekday::Wed),
+                        to: date(Weekday::Sun),
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let schedule = Schedule::for_category(&settings, &Category::Break);
+
+            assert!(schedule.is_excluded(date(Weekday::Tue)));
+            assert!(!is_alarm_due(&schedule, date(Weekday::Tue), 9, 30));
+            assert!(is_alarm_due(&schedule, date(Weekday::Mon), 9, 30));
+
+            //Tue is a holiday, Wed is in the vacation
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Mon), 10, 0),
+                alarm(next_week(Weekday::Mon), 9, 30)
+            );
+        }
+
+        #[test]
+        fn test_override_wins() {
+            let rule1 = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
+                interval: 30,
+                from: 9,
+                to: 10,
+                ..Default::default()
+            };
+
+            let rule2 = Rule {
+                serial: 2,
+                days: vec![],
+                interval: 15,
+                from: 14,
+                to: 15,
+                ..Default::default()
+            };
+
+            let settings = Settings {
+                rules: vec![rule1],
+                exceptions: Exceptions {
+                    dates: vec![date(Weekday::Mon)],
+                    overrides: vec![DateOverride {
+                        date: date(Weekday::Mon),
+                        rules: vec![rule2],
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let schedule = Schedule::for_category(&settings, &Category::Break);
+
+            assert!(!is_alarm_due(&schedule, date(Weekday::Mon), 9, 30));
+            assert!(is_alarm_due(&schedule, date(Weekday::Mon), 14, 45));
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Mon), 8, 0),
+                alarm(date(Weekday::Mon), 14, 15)
+            );
+        }
+
+        #[test]
+        fn test_expression_rules() {
+            let interval = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
+                interval: 30,
+                from: 9,
+                to: 10,
+                ..Default::default()
+            };
+
+            let cron = Rule {
+                serial: 2,
+                days: vec!["Mon".to_string()],
+                from: 9,
+                to: 11,
+                kind: RuleKind::Cron,
+                expression: Some("25,55 * * * *".to_string()),
+                ..Default::default()
+            };
+
+            let invalid = Rule {
+                serial: 3,
+                kind: RuleKind::Cron,
+                expression: Some("every 5 minutes".to_string()),
+                ..Default::default()
+            };
+
+            let rrule = Rule {
+                serial: 4,
+                from: 9,
+                to: 12,
+                kind: RuleKind::Rrule,
+                expression: Some("FREQ=MINUTELY;INTERVAL=90".to_string()),
+                ..Default::default()
+            };
+
+            assert!(validate_rule(&interval).is_ok());
+            assert!(validate_rule(&cron).is_ok());
+            assert!(validate_rule(&invalid).is_err());
+            assert!(validate_rule(&rrule).is_ok());
+
+            let settings = Settings {
+                rules: vec![interval, cron, invalid],
+                exceptions: Exceptions {
+                    overrides: vec![DateOverride {
+                        date: date(Weekday::Tue),
+                        rules: vec![rrule],
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let schedule = Schedule::for_category(&settings, &Category::Break);
+
+            assert_eq!(
+                schedule.alarms_on(date(Weekday::Mon)),
+                hashmap! {
+                    9 => vec![25, 30, 55],
+                    10 => vec![0, 25, 55],
+                }
+            );
+            assert_eq!(
+                schedule.alarms_on(date(Weekday::Tue)),
+                hashmap! {
+                    9 => vec![0],
+                    10 => vec![30],
+                    12 => vec![0],
+                }
+            );
+            assert!(schedule.alarms_on(date(Weekday::Wed)).is_empty());
+        }
+
+        #[test]
+        fn test_parse_holidays() {
+            let contents = "# public holidays\n2026-01-26 Republic Day\n\nnot-a-date\n2026-08-15\n";
+            assert_eq!(
+                parse_holidays(contents),
+                vec![
+                    NaiveDate::from_ymd_opt(2026, 1, 26).unwrap(),
+                    NaiveDate::from_ymd_opt(2026, 8, 15).unwrap()
+                ]
+            );
+        }
+
+        #[test]
+        fn test_meetings() {
+            let rule1 = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string()],
+                interval: 30,
+                from: 9,
+                to: 12,
+                ..Default::default()
+            };
+
+            let at = |h, m| date(Weekday::Mon).and_hms_opt(h, m, 0).unwrap();
+            let busy = vec![
+                BusyBlock {
+                    from: at(9, 50),
+                    to: at(10, 20),
+                    summary: "Standup".to_string(),
+                },
+                BusyBlock {
+                    from: at(10, 20),
+                    to: at(10, 40),
+                    summary: "Sync".to_string(),
+                },
+            ];
+
+            let mut settings = Settings {
+                rules: vec![rule1],
+                ..Default::default()
+            };
+
+            let mut schedule = Schedule::for_category(&settings, &Category::Break);
+            schedule.set_busy(busy.clone());
+            assert_eq!(
+                schedule.alarms_on(date(Weekday::Mon)),
+                hashmap! {
+                    9 => vec![30],
+                    11 => vec![0, 30],
+                    12 => vec![0],
+                }
+            );
+
+            //10:00 moves to the end of the standup and then of the sync,
+            //10:30 to the end of the sync
+            settings.meetings.action = MeetingAction::Defer;
+            let mut schedule = Schedule::for_category(&settings, &Category::Break);
+            schedule.set_busy(busy);
+            assert_eq!(
+                schedule.alarms_on(date(Weekday::Mon)),
+                hashmap! {
+                    9 => vec![30],
+                    10 => vec![40],
+                    11 => vec![0, 30],
+                    12 => vec![0],
+                }
+            );
+            assert_eq!(
+                find_next_alarm(&schedule, date(Weekday::Mon), 9, 45),
+                alarm(date(Weekday::Mon), 10, 40)
             );
         }
     }
 }
 
 pub use alarm_manager::*;
+pub use alarm_utils::{validate_rule, Schedule};
+pub use calendar::*;
+pub use escalation::{Alert, Response};
+pub use focus::FocusStatus;
+pub use ics_export::*;
+pub use pomodoro::*;
+pub use simulator::*;
+pub use stats::*;
+pub use timers::{timer_at, Timer};
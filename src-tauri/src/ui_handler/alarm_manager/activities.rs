@@ -0,0 +1,126 @@
+//! Things to do during a break, e.g. a stretch or an eye exercise. A library
+//! is bundled with the app and the user's own activities are added to it.
+//! The activity shown for an alarm is one of its category which fits in the
+//! break, preferring those not shown for the longest time
+mod activities {
+    use crate::ui_handler::{Activity, Category};
+
+    use log::debug;
+
+    const BUNDLED: &str = include_str!("activities.json");
+
+    /// The bundled activities with the user's added. A user activity with the
+    /// title of a bundled one replaces it
+    pub fn library(user: &[Activity]) -> Vec<Activity> {
+        let mut activities: Vec<Activity> = match serde_json::from_str(BUNDLED) {
+            Ok(a) => a,
+            Err(e) => {
+                debug!("activities: unable to read bundled activities: {}", e);
+                Vec::new()
+            }
+        };
+
+        activities.retain(|a| !user.iter().any(|u| u.title == a.title));
+        activities.extend(user.iter().cloned());
+        activities
+    }
+
+    /// Picks an activity of the category lasting at most `seconds`. `shown`
+    /// are titles of the activities shown before, most recent last. Those
+    /// never shown come first, then the one shown longest ago
+    pub fn pick<'a>(
+        activities: &'a [Activity],
+        category: &Category,
+        seconds: usize,
+        shown: &[String],
+    ) -> Option<&'a Activity> {
+        activities
+            .iter()
+            .filter(|a| a.category == *category && a.duration <= seconds)
+            .min_by_key(|a| shown.iter().rposition(|t| *t == a.title))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn activity(title: &str, duration: usize, category: Category) -> Activity {
+            Activity {
+                title: title.to_string(),
+                text: String::new(),
+                duration,
+                image: None,
+                category,
+            }
+        }
+
+        #[test]
+        fn test_library() {
+            let bundled = library(&[]);
+            assert!(!bundled.is_empty());
+            assert!(Category::ALL
+                .iter()
+                .filter(|c| **c != Category::Custom)
+                .all(|c| bundled.iter().any(|a| a.category == *c)));
+
+            let user = [
+                activity("Palming", 30, Category::Eyes),
+                activity("Juggling", 120, Category::Custom),
+            ];
+            let activities = library(&user);
+            assert_eq!(activities.len(), bundled.len() + 1);
+            assert_eq!(
+                activities.iter().filter(|a| a.title == "Palming").count(),
+                1
+            );
+            assert_eq!(&activities[activities.len() - 2..], &user);
+        }
+
+        #[test]
+        fn test_pick() {
+            let activities = [
+                activity("Walk", 300, Category::Break),
+                activity("Shoulders", 60, Category::Break),
+                activity("Window", 120, Category::Break),
+                activity("Far focus", 20, Category::Eyes),
+            ];
+            let title = |a: Option<&Activity>| a.map(|a| a.title.clone());
+
+            assert_eq!(
+                title(pick(&activities, &Category::Break, 300, &[])),
+                Some("Walk".to_string())
+            );
+            assert_eq!(
+                title(pick(&activities, &Category::Eyes, 20, &[])),
+                Some("Far focus".to_string())
+            );
+
+            //too long for the break
+            assert_eq!(
+                title(pick(&activities, &Category::Break, 90, &[])),
+                Some("Shoulders".to_string())
+            );
+            assert_eq!(pick(&activities, &Category::Break, 30, &[]), None);
+            assert_eq!(pick(&activities, &Category::Water, 300, &[]), None);
+
+            //not shown before, then shown longest ago
+            let shown = ["Walk".to_string(), "Shoulders".to_string()];
+            assert_eq!(
+                title(pick(&activities, &Category::Break, 300, &shown)),
+                Some("Window".to_string())
+            );
+            let shown = [
+                "Walk".to_string(),
+                "Window".to_string(),
+                "Shoulders".to_string(),
+                "Walk".to_string(),
+            ];
+            assert_eq!(
+                title(pick(&activities, &Category::Break, 300, &shown)),
+                Some("Window".to_string())
+            );
+        }
+    }
+}
+
+pub use activities::*;
@@ -1,70 +1,535 @@
 mod alarm_utils {
-    use crate::ui_handler::{Alarm, Rule};
+    use super::super::calendar::BusyBlock;
+    use super::super::expression::ExpressionRule;
+    use super::super::ramp::{ramp_times, validate_ramp};
+    use super::super::random::{random_times, validate_random};
+    use super::super::rrule::matches_nth_weekday;
+    use crate::ui_handler::{
+        Alarm, Category, Exceptions, MeetingAction, Random, Recurrence, Rule, RuleKind, Settings,
+        TimeWindow,
+    };
     // use crate::Rule;
-    use chrono::Weekday;
+    use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Weekday};
     use log::debug;
+    use serde::Serialize;
+    use std::cmp::Reverse;
     use std::collections::HashMap;
 
-    /// Given a set of alarms and current hour and minutes, determine next scheduled alarm
+    //how far find_next_alarm looks ahead, enough for monthly rules and
+    //rules which become valid later
+    pub const NEXT_ALARM_HORIZON_DAYS: usize = 366;
+
+    const MINUTES_PER_DAY: i64 = 24 * 60;
+
+    //length of a break: the window in which it counts as taken, the length
+    //of exported events and what a focus session makes up for per break
+    pub const BREAK_MINUTES: i64 = 5;
+
+    /// An alarm dropped because it came within the minimum gap of an alarm
+    /// of a higher priority (or, on a tie, earlier) rule
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Merge {
+        pub hour: usize,
+        pub min: usize,
+        pub serial: usize,
+        pub into_hour: usize,
+        pub into_min: usize,
+        pub into_serial: usize,
+    }
+
+    /// Rules split by how their alarms are computed. Invalid rules are skipped
+    #[derive(Debug, Default)]
+    struct RuleSet {
+        intervals: Vec<Rule>,
+        expressions: Vec<(Rule, ExpressionRule)>,
+        random: Vec<Rule>,
+        ramped: Vec<Rule>,
+    }
+
+    impl RuleSet {
+        fn new(rules: &[Rule]) -> Self {
+            let mut set = Self::default();
+            set.extend(rules);
+            set
+        }
+
+        fn extend(&mut self, rules: &[Rule]) {
+            for r in rules {
+                if let Err(e) = validate_rule(r) {
+                    debug!("alarm_utils: skipping rule {}: {}", r.serial, e);
+                    continue;
+                }
+
+                match r.kind {
+                    RuleKind::Interval if r.random.is_some() => self.random.push(r.clone()),
+                    RuleKind::Interval if r.ramp.is_some() => self.ramped.push(r.clone()),
+                    RuleKind::Interval => self.intervals.push(r.clone()),
+                    _ => {
+                        if let Ok(e) = ExpressionRule::new(r) {
+                            self.expressions.push((r.clone(), e));
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Alarms of all rules on a date, as minutes since midnight along
+        /// with the rule producing them. `skipped` are the minutes at which
+        /// breaks were skipped that day
+        fn alarms_on(&self, date: NaiveDate, skipped: &[usize]) -> Vec<(usize, &Rule)> {
+            let on_day = |r: &Rule| r.days.iter().any(|d| get_weekday(d) == Ok(date.weekday()));
+            let mut alarms = Vec::new();
+
+            for r in self
+                .intervals
+                .iter()
+                .filter(|r| on_day(r) && is_active(r, date))
+            {
+                alarms.extend(interval_times(r).iter().map(|(h, m)| (h * 60 + m, r)));
+            }
+
+            for (r, e) in self.expressions.iter().filter(|(r, _)| is_active(r, date)) {
+                alarms.extend(e.occurrences(date).iter().map(|(h, m)| (h * 60 + m, r)));
+            }
+
+            for r in self.random.iter().filter(|r| is_active(r, date)) {
+                alarms.extend(random_times(r, date).iter().map(|(h, m)| (h * 60 + m, r)));
+            }
+
+            for r in self
+                .ramped
+                .iter()
+                .filter(|r| on_day(r) && is_active(r, date))
+            {
+                let times = ramp_times(r, date, skipped);
+                alarms.extend(times.iter().map(|(h, m)| (h * 60 + m, r)));
+            }
+
+            alarms.retain(|(t, r)| !in_windows(&r.exclusions, *t));
+            alarms
+        }
+    }
+
+    /// Alarms of the rules along with the dates on which they do not apply
+    #[derive(Debug, Default)]
+    pub struct Schedule {
+        category: Category,
+        rules: RuleSet,
+        exceptions: Exceptions,
+        holidays: Vec<NaiveDate>,
+        overrides: HashMap<NaiveDate, RuleSet>,
+        busy: Vec<BusyBlock>,
+        meeting_action: MeetingAction,
+        min_gap: usize,
+        quiet_hours: Vec<TimeWindow>,
+        //times at which breaks were skipped, for ramped rules
+        skipped: Vec<NaiveDateTime>,
+    }
+
+    impl Schedule {
+        /// Schedule of the rules of one category. Exceptions, meetings and
+        /// quiet hours apply to all categories, the minimum gap to breaks only
+        pub fn for_category(settings: &Settings, category: &Category) -> Self {
+            let in_category = |r: &&Rule| r.category == *category;
+
+            let holidays = match &settings.exceptions.holiday_file {
+                Some(path) => read_holiday_file(path),
+                None => Vec::new(),
+            };
+
+            let mut overrides: HashMap<NaiveDate, RuleSet> = HashMap::new();
+            for o in &settings.exceptions.overrides {
+                let weekday = o.date.weekday();
+                let rules: Vec<Rule> = o
+                    .rules
+                    .iter()
+                    .filter(in_category)
+                    .map(|r| Rule {
+                        days: vec![format!("{:?}", weekday)],
+                        ..r.clone()
+                    })
+                    .collect();
+
+                overrides.entry(o.date).or_default().extend(&rules);
+            }
+
+            let rules: Vec<Rule> = settings.rules.iter().filter(in_category).cloned().collect();
+
+            Self {
+                category: category.clone(),
+                rules: RuleSet::new(&rules),
+                exceptions: settings.exceptions.clone(),
+                holidays,
+                overrides,
+                busy: Vec::new(),
+                meeting_action: settings.meetings.action.clone(),
+                //micro-breaks come more often than any sensible gap
+                min_gap: match category {
+                    Category::Break => settings.min_gap,
+                    _ => 0,
+                },
+                quiet_hours: settings.quiet_hours.clone(),
+                skipped: Vec::new(),
+            }
+        }
+
+        pub fn category(&self) -> &Category {
+            &self.category
+        }
+
+        pub fn set_busy(&mut self, busy: Vec<BusyBlock>) {
+            self.busy = busy;
+        }
+
+        /// Breaks skipped so far. Ramped rules shorten their interval after
+        /// these on the same day
+        pub fn set_skipped(&mut self, skipped: Vec<NaiveDateTime>) {
+            self.skipped = skipped;
+        }
+
+        /// Busy blocks overlapping the given window
+        pub fn busy_between(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<BusyBlock> {
+            self.busy
+                .iter()
+                .filter(|b| b.from < to && b.to > from)
+                .cloned()
+                .collect()
+        }
+
+        /// Hours and minutes at which alarms are to be played on a date.
+        /// An override for the date wins over exceptions and weekday rules.
+        /// Alarms inside busy blocks are dropped or moved to the block's end.
+        /// Alarms in quiet hours are dropped
+        pub fn alarms_on(&self, date: NaiveDate) -> HashMap<usize, Vec<usize>> {
+            self.preview_on(date).0
+        }
+
+        /// Same as alarms_on, along with the alarms merged into others
+        /// for being closer than the minimum gap. The gap is also kept from
+        /// the last alarm of the day before
+        pub fn preview_on(&self, date: NaiveDate) -> (HashMap<usize, Vec<usize>>, Vec<Merge>) {
+            let (kept, merges) = self.kept_on(date);
+
+            let mut hour_map: HashMap<usize, Vec<usize>> = HashMap::new();
+            for (t, _) in kept {
+                hour_map.entry(t / 60).or_default().push(t % 60);
+            }
+
+            for mins in hour_map.values_mut() {
+                mins.sort();
+                mins.dedup();
+            }
+
+            (hour_map, merges)
+        }
+
+        /// Serial of the rule whose alarm is played at the hour and minute
+        pub fn rule_at(&self, date: NaiveDate, hour: usize, minute: usize) -> Option<usize> {
+            self.kept_on(date)
+                .0
+                .into_iter()
+                .find(|(t, _)| *t == hour * 60 + minute)
+                .map(|(_, r)| r.serial)
+        }
+
+        /// Alarms on a date with the rules they come from, once those too
+        /// close to each other are merged
+        fn kept_on(&self, date: NaiveDate) -> (Vec<(usize, &Rule)>, Vec<Merge>) {
+            //only the day before is looked at, not what merged into its alarms
+            let previous = match date.pred_opt() {
+                Some(d) if self.min_gap > 0 => coalesce(self.candidates(d), self.min_gap, None)
+                    .0
+                    .into_iter()
+                    .max_by_key(|(t, _)| *t),
+                _ => None,
+            };
+
+            coalesce(self.candidates(date), self.min_gap, previous)
+        }
+
+        /// Alarms on a date before those too close to each other are merged
+        fn candidates(&self, date: NaiveDate) -> Vec<(usize, &Rule)> {
+            let rules = match self.overrides.get(&date) {
+                Some(rules) => rules,
+                None if self.is_excluded(date) => return Vec::new(),
+                None => &self.rules,
+            };
+
+            let skipped: Vec<usize> = self
+                .skipped
+                .iter()
+                .filter(|t| t.date() == date)
+                .map(|t| t.hour() as usize * 60 + t.minute() as usize)
+                .collect();
+
+            rules
+                .alarms_on(date, &skipped)
+                .into_iter()
+                .filter_map(|(t, r)| self.after_meetings(date, t).map(|t| (t, r)))
+                .filter(|(t, _)| !in_windows(&self.quiet_hours, *t))
+                .collect()
+        }
+
+        /// Time of an alarm once meetings are accounted for. None if it is
+        /// suppressed or deferred past midnight
+        fn after_meetings(&self, date: NaiveDate, minute: usize) -> Option<usize> {
+            if self.busy.is_empty() {
+                return Some(minute);
+            }
+
+            let mut t = date.and_hms_opt((minute / 60) as u32, (minute % 60) as u32, 0)?;
+            while let Some(b) = self.busy.iter().find(|b| b.from <= t && t < b.to) {
+                match self.meeting_action {
+                    MeetingAction::Suppress => return None,
+                    MeetingAction::Defer => t = b.to,
+                }
+            }
+
+            //alarms deferred past midnight are dropped
+            if t.date() != date {
+                return None;
+            }
+
+            Some(t.hour() as usize * 60 + t.minute() as usize)
+        }
+
+        pub fn is_excluded(&self, date: NaiveDate) -> bool {
+            self.exceptions.dates.contains(&date)
+                || self
+                    .exceptions
+                    .ranges
+                    .iter()
+                    .any(|r| r.from <= date && date <= r.to)
+                || self.holidays.contains(&date)
+        }
+    }
+
+    /// Drops alarms closer than `min_gap` minutes to an alarm which is kept.
+    /// Alarms of higher priority rules are kept first, then earlier ones.
+    /// `previous` is the last alarm of the day before, which is always kept.
+    /// Returns the kept alarms and the merges. A gap of 0 only merges alarms
+    /// at the same minute, and these are not reported
+    fn coalesce<'a>(
+        mut alarms: Vec<(usize, &'a Rule)>,
+        min_gap: usize,
+        previous: Option<(usize, &'a Rule)>,
+    ) -> (Vec<(usize, &'a Rule)>, Vec<Merge>) {
+        alarms.sort_by_key(|(t, r)| (Reverse(r.priority), *t, r.serial));
+
+        //minutes from midnight, negative for the day before
+        let mut kept: Vec<(i64, &Rule)> = previous
+            .map(|(t, r)| (t as i64 - MINUTES_PER_DAY, r))
+            .into_iter()
+            .collect();
+        let mut merges = Vec::new();
+
+        for (t, r) in alarms {
+            let into = kept
+                .iter()
+                .filter(|(k, _)| k.abs_diff(t as i64) < min_gap.max(1) as u64)
+                .min_by_key(|(k, _)| k.abs_diff(t as i64));
+
+            match into {
+                Some((k, into)) if min_gap > 0 => {
+                    let k = k.rem_euclid(MINUTES_PER_DAY) as usize;
+                    merges.push(Merge {
+                        hour: t / 60,
+                        min: t % 60,
+                        serial: r.serial,
+                        into_hour: k / 60,
+                        into_min: k % 60,
+                        into_serial: into.serial,
+                    })
+                }
+                Some(_) => (),
+                None => kept.push((t as i64, r)),
+            }
+        }
+
+        merges.sort_by_key(|m| (m.hour, m.min, m.serial));
+        let kept = kept
+            .into_iter()
+            .filter(|(t, _)| *t >= 0)
+            .map(|(t, r)| (t as usize, r))
+            .collect();
+        (kept, merges)
+    }
+
+    /// Checks if a rule is valid on a date and the date falls in one of the
+    /// weeks the rule recurs in. The rule's days are not checked
+    pub fn is_active(rule: &Rule, date: NaiveDate) -> bool {
+        if rule.valid_from.is_some_and(|from| date < from)
+            || rule.valid_until.is_some_and(|until| date > until)
+        {
+            return false;
+        }
+
+        match &rule.recurrence {
+            Recurrence::Week => true,
+            Recurrence::Weeks { weeks, start } => {
+                let monday =
+                    |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+                let n = (monday(date) - monday(*start)).num_weeks();
+                *weeks > 0 && n.rem_euclid(*weeks as i64) == 0
+            }
+            Recurrence::Month { weeks } => weeks
+                .iter()
+                .any(|n| matches_nth_weekday(date, Some(*n), date.weekday())),
+        }
+    }
+
+    /// Checks if a time, in minutes since midnight, falls in any of the windows
+    pub fn in_windows(windows: &[TimeWindow], minute: usize) -> bool {
+        let t = minute as u32 * 60;
+        windows.iter().any(|w| {
+            let from = w.from.num_seconds_from_midnight();
+            let to = w.to.num_seconds_from_midnight();
+
+            if from <= to {
+                from <= t && t < to
+            } else {
+                from <= t || t < to
+            }
+        })
+    }
+
+    /// Checks that a rule can be scheduled. Returns the reason if it can not
+    pub fn validate_rule(rule: &Rule) -> Result<(), String> {
+        if let (Some(from), Some(until)) = (rule.valid_from, rule.valid_until) {
+            if from > until {
+                return Err("Rule must be valid from a date before it is valid until".to_string());
+            }
+        }
+
+        match &rule.recurrence {
+            Recurrence::Weeks { weeks: 0, .. } => {
+                return Err("Rule must recur at least every week".to_string());
+            }
+            Recurrence::Month { weeks } => {
+                if weeks.is_empty() {
+                    return Err("At least one week of the month is required".to_string());
+                }
+
+                if let Some(n) = weeks.iter().find(|n| **n == 0 || n.abs() > 5) {
+                    return Err(format!("Invalid week of the month {}", n));
+                }
+            }
+            _ => (),
+        }
+
+        match rule.kind {
+            RuleKind::Interval => {
+                for d in &rule.days {
+                    get_weekday(d)?;
+                }
+
+                //random breaks don't use the interval
+                let uses_interval = !matches!(rule.random, Some(Random::Breaks { .. }));
+                if uses_interval && rule.interval == 0 {
+                    return Err("Interval must be at least a minute".to_string());
+                }
+
+                if rule.anchor.offset >= 60 {
+                    return Err("Offset must be less than an hour".to_string());
+                }
+
+                //the window can't run past midnight
+                if rule.to > 24 {
+                    return Err("Window must end by hour 24".to_string());
+                }
+
+                if rule.from > rule.to {
+                    return Err("Window must start before it ends".to_string());
+                }
+
+                validate_random(rule)?;
+                validate_ramp(rule)
+            }
+            _ if rule.random.is_some() => Err("Only interval rules can be randomised".to_string()),
+            _ if rule.ramp.is_some() => Err("Only interval rules can be ramped".to_string()),
+            _ => ExpressionRule::new(rule).map(|_| ()),
+        }
+    }
+
+    /// Given a schedule and current date, hour and minutes, determine next
+    /// scheduled alarm. Looks up to NEXT_ALARM_HORIZON_DAYS ahead
     pub fn find_next_alarm(
-        alarms: &HashMap<Weekday, HashMap<usize, Vec<usize>>>,
-        current_day: Weekday,
+        schedule: &Schedule,
+        today: NaiveDate,
         current_hour: usize,
         current_minute: usize,
     ) -> Option<Alarm> {
-        let mut current_day_to_check;
-        let mut next_alarm: Option<Alarm> = None;
-
-        // Start with current_day, check if alarms are scheduled after current time.
-        // Repeat for successive days until we wrap around to current_day
-        if let Some(hour_map) = alarms.get(&current_day) {
-            if let Some(hour_mins) = find_next_for_today(hour_map, current_hour, current_minute) {
-                //next_alarm = Some((current_day, hour_mins.0, hour_mins.1));
-                next_alarm = Some(Alarm {
-                    day: current_day,
-                    hour: hour_mins.0,
-                    min: hour_mins.1,
+        // Start with today, check if alarms are scheduled after current time
+        let hour_map = schedule.alarms_on(today);
+        if let Some((hour, min)) = find_next_for_today(&hour_map, current_hour, current_minute) {
+            return Some(Alarm {
+                day: today.weekday(),
+                date: today,
+                hour,
+                min,
+                category: schedule.category().clone(),
+            });
+        }
+
+        //try searching other days
+        for date in today.iter_days().skip(1).take(NEXT_ALARM_HORIZON_DAYS) {
+            let hour_map = schedule.alarms_on(date);
+            let first = hour_map
+                .iter()
+                .filter_map(|(hour, mins)| mins.first().map(|min| (*hour, *min)))
+                .min();
+
+            if let Some((hour, min)) = first {
+                return Some(Alarm {
+                    day: date.weekday(),
+                    date,
+                    hour,
+                    min,
+                    category: schedule.category().clone(),
                 });
             }
         }
 
-        if next_alarm.is_some() {
-            //we found our next alarm on the same day
-            return next_alarm;
-        }
+        None
+    }
 
-        //try searching other days
-        current_day_to_check = current_day.succ();
-        let mut days = 0;
-        loop {
-            if let Some(hour_map) = alarms.get(&current_day_to_check) {
-                let mut sorted_keys: Vec<usize> = hour_map.keys().cloned().collect();
-                sorted_keys.sort();
-
-                for hour in &sorted_keys {
-                    if let Some(mins) = hour_map.get(hour) {
-                        for min in mins.iter() {
-                            //mins are sorted. just pick up the first
-                            return Some(Alarm {
-                                day: current_day_to_check,
-                                hour: *hour,
-                                min: *min,
-                            });
-                        }
+    /// Checks if an alarm is scheduled for the given date, hour and minute
+    pub fn is_alarm_due(schedule: &Schedule, date: NaiveDate, hour: usize, minute: usize) -> bool {
+        schedule
+            .alarms_on(date)
+            .get(&hour)
+            .is_some_and(|mins| mins.contains(&minute))
+    }
+
+    /// Parses the contents of a holiday file. One YYYY-MM-DD date per line,
+    /// optionally followed by a description. Lines starting with # are ignored
+    pub fn parse_holidays(contents: &str) -> Vec<NaiveDate> {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .filter_map(|l| {
+                let date = l.split_whitespace().next()?;
+                match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                    Ok(d) => Some(d),
+                    Err(_) => {
+                        debug!("alarm_utils: invalid holiday {}", l);
+                        None
                     }
                 }
-            }
-
-            current_day_to_check = current_day_to_check.succ();
+            })
+            .collect()
+    }
 
-            days += 1;
-            if days == 8 {
-                //wrapped around
-                break;
+    fn read_holiday_file(path: &str) -> Vec<NaiveDate> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => parse_holidays(&contents),
+            Err(e) => {
+                debug!("alarm_utils: unable to read {}: {}", path, e);
+                Vec::new()
             }
         }
-
-        return next_alarm;
     }
 
     fn find_next_for_today(
@@ -98,74 +563,68 @@ mod alarm_utils {
     }
 
     /// For a set of rules, finds the hours and minutes for each day at which
-    /// alarm should be played. Assumes that rules are not overlapping
+    /// alarm should be played. Overlapping rules are merged
     pub fn get_alarms(rules: &[Rule]) -> HashMap<Weekday, HashMap<usize, Vec<usize>>> {
         let mut alarms: HashMap<Weekday, HashMap<usize, Vec<usize>>> = HashMap::new();
 
         for r in rules {
-            for d in &r.days {
-                let weekday = get_weekday(d).unwrap();
-                let hours = alarms.entry(weekday).or_insert_with(HashMap::new);
-
-                let s = r.from;
-                let e = r.to;
-                let f = r.interval;
-                let hrs = get_hours(s, e);
-
-                let mut i = 0;
-                let mut m = f;
-                let mut h;
-
-                loop {
-                    let mut mins = Vec::new();
+            let times: Vec<(usize, usize)> = interval_times(r)
+                .into_iter()
+                .filter(|(h, m)| !in_windows(&r.exclusions, h * 60 + m))
+                .collect();
 
-                    loop {
-                        mins.push(m % 60);
-                        m += f;
-
-                        if m - (60 * i) >= 60 {
-                            h = hrs[i];
-
-                            if f == 60 && h == s {
-                                i += 1;
-                                break;
-                            }
-
-                            hours.entry(h).or_insert_with(Vec::new).extend(mins.iter());
-                            debug!("{} h: {} mins: {:?}", d, h, mins);
-
-                            i += 1;
-                            break;
-                        }
+            for d in &r.days {
+                let weekday = match get_weekday(d) {
+                    Ok(weekday) => weekday,
+                    Err(e) => {
+                        debug!("alarm_utils: skipping day {}: {}", d, e);
+                        continue;
                     }
+                };
+                let hours = alarms.entry(weekday).or_default();
 
-                    if e == hrs[i] {
-                        if m % 60 == 0 {
-                            hours.entry(e).or_insert_with(|| vec![0]);
-                            debug!("{} h: {} mins: {:?}", d, e, hours[&e]);
-                        }
-                        break;
-                    }
+                for (h, m) in &times {
+                    hours.entry(*h).or_default().push(*m);
                 }
+                debug!("{} times: {:?}", d, times);
+            }
+        }
+
+        for hours in alarms.values_mut() {
+            for mins in hours.values_mut() {
+                mins.sort();
+                mins.dedup();
             }
         }
 
         alarms
     }
 
-    fn get_hours(s: usize, e: usize) -> Vec<usize> {
-        let mut hrs = Vec::new();
-
-        let mut current_hour = s;
-        while current_hour <= e {
-            hrs.push(current_hour);
-            current_hour += 1;
+    /// Hours and minutes of an interval rule. Alarms are every `interval`
+    /// minutes counted from the start of the window. The rule's anchor
+    /// decides whether the start and end of the window get alarms
+    pub fn interval_times(r: &Rule) -> Vec<(usize, usize)> {
+        if r.interval == 0 {
+            return Vec::new();
         }
 
-        hrs
+        let start = r.from * 60 + r.anchor.offset;
+        let end = r.to * 60 + r.anchor.offset;
+
+        let first = if r.anchor.include_start {
+            start
+        } else {
+            start + r.interval
+        };
+
+        (first..=end.min(24 * 60 - 1))
+            .step_by(r.interval)
+            .filter(|t| r.anchor.include_end || *t != end)
+            .map(|t| (t / 60, t % 60))
+            .collect()
     }
 
-    fn get_weekday(d: &str) -> Result<Weekday, String> {
+    pub fn get_weekday(d: &str) -> Result<Weekday, String> {
         match d {
             "Mon" => Ok(Weekday::Mon),
             "Tue" => Ok(Weekday::Tue),
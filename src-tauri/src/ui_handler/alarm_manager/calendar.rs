@@ -0,0 +1,349 @@
+//! Busy blocks from local iCalendar (.ics) files e.g. exported meeting
+//! calendars. Alarms falling inside a busy block are dropped or moved to
+//! the end of the block, depending on settings.
+mod calendar {
+    use super::super::rrule::*;
+
+    use chrono::{Duration, NaiveDateTime, TimeZone, Utc};
+    use ical::parser::ical::component::IcalEvent;
+    use ical::property::Property;
+    use ical::IcalParser;
+    use log::debug;
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::io::BufReader;
+    use std::time::SystemTime;
+
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct BusyBlock {
+        pub from: NaiveDateTime,
+        pub to: NaiveDateTime,
+        pub summary: String,
+    }
+
+    /// Timezone in which the times of an event are given
+    enum EventTz {
+        Utc,
+        Named(chrono_tz::Tz),
+        Floating,
+    }
+
+    impl EventTz {
+        /// Converts to wall clock time of `tz`
+        fn convert<Tz: TimeZone>(&self, t: NaiveDateTime, tz: &Tz) -> Option<NaiveDateTime> {
+            match self {
+                EventTz::Utc => Some(Utc.from_utc_datetime(&t).with_timezone(tz).naive_local()),
+                EventTz::Named(z) => z
+                    .from_local_datetime(&t)
+                    .earliest()
+                    .map(|t| t.with_timezone(tz).naive_local()),
+                EventTz::Floating => Some(t),
+            }
+        }
+    }
+
+    /// Reads busy blocks from the given files. Unreadable files are skipped
+    pub fn read_busy_blocks<Tz: TimeZone>(
+        files: &[String],
+        tz: &Tz,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<BusyBlock> {
+        let mut blocks = Vec::new();
+
+        for path in files {
+            match std::fs::read_to_string(path) {
+                Ok(contents) => blocks.extend(parse_busy_blocks(&contents, tz, from, to)),
+                Err(e) => debug!("calendar: unable to read {}: {}", path, e),
+            }
+        }
+
+        blocks.sort_by_key(|b| b.from);
+        blocks
+    }
+
+    /// Modification times of the given files, used to detect changes
+    pub fn modified_times(files: &[String]) -> Vec<Option<SystemTime>> {
+        files
+            .iter()
+            .map(|f| std::fs::metadata(f).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// Busy blocks of the VEVENTs in an iCalendar which overlap the window
+    /// between `from` and `to`. Times are wall clock times of `tz`.
+    /// All-day, transparent and cancelled events are not considered busy
+    pub fn parse_busy_blocks<Tz: TimeZone>(
+        contents: &str,
+        tz: &Tz,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<BusyBlock> {
+        let mut events = Vec::new();
+
+        for calendar in IcalParser::new(BufReader::new(contents.as_bytes())) {
+            match calendar {
+                Ok(c) => events.extend(c.events),
+                Err(e) => {
+                    debug!("calendar: {}", e);
+                    break;
+                }
+            }
+        }
+
+        //modified instances of a recurring event replace the original occurrence
+        let mut replaced: HashMap<String, Vec<NaiveDateTime>> = HashMap::new();
+        for e in &events {
+            let uid = value(e, "UID");
+            let rid = property(e, "RECURRENCE-ID").and_then(parse_time);
+            if let (Some(uid), Some((t, _))) = (uid, rid) {
+                replaced.entry(uid).or_default().push(t);
+            }
+        }
+
+        let mut blocks: Vec<BusyBlock> = events
+            .iter()
+            .flat_map(|e| event_blocks(e, &replaced, tz, from, to))
+            .collect();
+
+        blocks.sort_by_key(|b| b.from);
+        blocks
+    }
+
+    fn event_blocks<Tz: TimeZone>(
+        event: &IcalEvent,
+        replaced: &HashMap<String, Vec<NaiveDateTime>>,
+        tz: &Tz,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<BusyBlock> {
+        if value(event, "TRANSP").as_deref() == Some("TRANSPARENT")
+            || value(event, "STATUS").as_deref() == Some("CANCELLED")
+        {
+            return Vec::new();
+        }
+
+        let (start, start_tz) = match property(event, "DTSTART").and_then(parse_time) {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+
+        let duration = match property(event, "DTEND").and_then(parse_time) {
+            Some((end, end_tz)) => match (end_tz.convert(end, tz), start_tz.convert(start, tz)) {
+                (Some(e), Some(s)) => e - s,
+                _ => return Vec::new(),
+            },
+            None => value(event, "DURATION")
+                .and_then(|d| parse_duration(&d))
+                .unwrap_or_else(Duration::zero),
+        };
+
+        if duration <= Duration::zero() {
+            return Vec::new();
+        }
+
+        let mut excluded: Vec<NaiveDateTime> = event
+            .properties
+            .iter()
+            .filter(|p| p.name == "EXDATE")
+            .flat_map(|p| {
+                let tzid = param(p, "TZID");
+                p.value
+                    .iter()
+                    .flat_map(|v| v.split(','))
+                    .filter_map(move |v| parse_time_value(v, tzid))
+                    .map(|(t, _)| t)
+                    .collect::<Vec<NaiveDateTime>>()
+            })
+            .collect();
+
+        let is_instance = property(event, "RECURRENCE-ID").is_some();
+        if !is_instance {
+            if let Some(times) = value(event, "UID").and_then(|uid| replaced.get(&uid)) {
+                excluded.extend(times);
+            }
+        }
+
+        // Recurrences are expanded in the event's timezone. Widen the window
+        // to cover any difference between that and the local timezone
+        let margin = duration + Duration::days(2);
+        let starts = match value(event, "RRULE") {
+            Some(r) if !is_instance => match RRule::parse(&r) {
+                Ok(rule) => rule.between(start, from - margin, to + margin),
+                Err(e) => {
+                    debug!("calendar: {}: {}", r, e);
+                    vec![start]
+                }
+            },
+            _ => vec![start],
+        };
+
+        let summary = value(event, "SUMMARY").unwrap_or_default();
+        starts
+            .into_iter()
+            .filter(|s| !excluded.contains(s))
+            .filter_map(|s| start_tz.convert(s, tz))
+            .map(|s| BusyBlock {
+                from: s,
+                to: s + duration,
+                summary: summary.clone(),
+            })
+            .filter(|b| b.from < to && b.to > from)
+            .collect()
+    }
+
+    fn property<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a Property> {
+        event.properties.iter().find(|p| p.name == name)
+    }
+
+    fn value(event: &IcalEvent, name: &str) -> Option<String> {
+        property(event, name).and_then(|p| p.value.clone())
+    }
+
+    fn param<'a>(p: &'a Property, name: &str) -> Option<&'a str> {
+        p.params
+            .as_ref()?
+            .iter()
+            .find(|(k, _)| k == name)
+            .and_then(|(_, v)| v.first())
+            .map(|v| v.as_str())
+    }
+
+    /// Date-time of a property. None for dates, as used by all-day events
+    fn parse_time(p: &Property) -> Option<(NaiveDateTime, EventTz)> {
+        if param(p, "VALUE") == Some("DATE") {
+            return None;
+        }
+
+        parse_time_value(p.value.as_ref()?, param(p, "TZID"))
+    }
+
+    fn parse_time_value(v: &str, tzid: Option<&str>) -> Option<(NaiveDateTime, EventTz)> {
+        let v = v.trim();
+        if let Some(utc) = v.strip_suffix('Z') {
+            let t = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+            return Some((t, EventTz::Utc));
+        }
+
+        let t = NaiveDateTime::parse_from_str(v, "%Y%m%dT%H%M%S").ok()?;
+        match tzid.and_then(|id| id.parse::<chrono_tz::Tz>().ok()) {
+            Some(z) => Some((t, EventTz::Named(z))),
+            None => Some((t, EventTz::Floating)),
+        }
+    }
+
+    /// Parses durations like PT1H30M, P1D or P1W
+    fn parse_duration(s: &str) -> Option<Duration> {
+        let s = s.trim();
+        let s = s.strip_prefix('+').unwrap_or(s).strip_prefix('P')?;
+
+        let mut total = Duration::zero();
+        let mut num = String::new();
+        let mut in_time = false;
+
+        for c in s.chars() {
+            match c {
+                '0'..='9' => num.push(c),
+                'T' => in_time = true,
+                _ => {
+                    let n: i64 = num.parse().ok()?;
+                    num.clear();
+
+                    total += match (c, in_time) {
+                        ('W', false) => Duration::weeks(n),
+                        ('D', false) => Duration::days(n),
+                        ('H', true) => Duration::hours(n),
+                        ('M', true) => Duration::minutes(n),
+                        ('S', true) => Duration::seconds(n),
+                        _ => return None,
+                    };
+                }
+            }
+        }
+
+        Some(total)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::NaiveDate;
+        use chrono_tz::Asia::Kolkata;
+
+        const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+DTSTART;TZID=Europe/Berlin:20260105T100000\r
+DTEND;TZID=Europe/Berlin:20260105T101500\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r
+EXDATE;TZID=Europe/Berlin:20260107T100000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup (moved)\r
+RECURRENCE-ID;TZID=Europe/Berlin:20260106T100000\r
+DTSTART;TZID=Europe/Berlin:20260106T110000\r
+DTEND;TZID=Europe/Berlin:20260106T111500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:review\r
+SUMMARY:Review\r
+DTSTART:20260108T140000Z\r
+DURATION:PT1H\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:offsite\r
+SUMMARY:Offsite\r
+DTSTART;VALUE=DATE:20260109\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:focus\r
+SUMMARY:Focus\r
+TRANSP:TRANSPARENT\r
+DTSTART:20260109T090000Z\r
+DTEND:20260109T120000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+        fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 1, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        }
+
+        #[test]
+        fn test_parse_busy_blocks() {
+            let blocks = parse_busy_blocks(ICS, &Kolkata, at(5, 0, 0), at(10, 0, 0));
+            let times: Vec<(NaiveDateTime, NaiveDateTime, &str)> = blocks
+                .iter()
+                .map(|b| (b.from, b.to, b.summary.as_str()))
+                .collect();
+
+            //Berlin is 4:30 hours behind Kolkata in January
+            assert_eq!(
+                times,
+                vec![
+                    (at(5, 14, 30), at(5, 14, 45), "Standup"),
+                    (at(6, 15, 30), at(6, 15, 45), "Standup (moved)"),
+                    (at(8, 14, 30), at(8, 14, 45), "Standup"),
+                    (at(8, 19, 30), at(8, 20, 30), "Review"),
+                    (at(9, 14, 30), at(9, 14, 45), "Standup"),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_parse_duration() {
+            assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+            assert_eq!(parse_duration("P1W"), Some(Duration::days(7)));
+            assert_eq!(parse_duration("P1DT2H"), Some(Duration::hours(26)));
+            assert_eq!(parse_duration("1H"), None);
+        }
+    }
+}
+
+pub use calendar::*;
@@ -0,0 +1,127 @@
+//! Breaks which keep alerting until they are answered. Without an answer
+//! the alert moves up a level every minute, or every fifth of the break for
+//! longer ones: the sound again, then a notification, then an overlay, each
+//! time louder. Levels stop rising at MAX_LEVEL in case nobody is there to
+//! answer
+mod escalation {
+    use chrono::NaiveDateTime;
+    use serde::Serialize;
+
+    pub const MAX_LEVEL: usize = 5;
+
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Stage {
+        Sound,
+        Notification,
+        Overlay,
+    }
+
+    /// A break played but not yet answered
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Alert {
+        //minute of the alarm, as recorded in the history
+        pub at: NaiveDateTime,
+        pub played: NaiveDateTime,
+        pub level: usize,
+        //length of the break
+        pub seconds: usize,
+    }
+
+    /// How a break was answered
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Response {
+        pub at: NaiveDateTime,
+        //seconds from the alarm to the answer
+        pub seconds: i64,
+        pub skipped: bool,
+    }
+
+    impl Alert {
+        pub fn new(at: NaiveDateTime, played: NaiveDateTime, seconds: usize) -> Self {
+            Self {
+                at,
+                played,
+                level: 0,
+                seconds,
+            }
+        }
+
+        pub fn stage(&self) -> Stage {
+            match self.level {
+                0 => Stage::Sound,
+                1 => Stage::Notification,
+                _ => Stage::Overlay,
+            }
+        }
+
+        /// Moves up a level when the next one is due by `now`. Returns false
+        /// if it isn't, or once at MAX_LEVEL
+        pub fn escalate(&mut self, now: NaiveDateTime) -> bool {
+            let step = (self.seconds / MAX_LEVEL).max(60) as i64;
+            let due = (now - self.played).num_seconds() / step;
+            if self.level >= MAX_LEVEL || due <= self.level as i64 {
+                return false;
+            }
+
+            self.level += 1;
+            true
+        }
+
+        pub fn respond(&self, now: NaiveDateTime, skipped: bool) -> Response {
+            Response {
+                at: self.at,
+                seconds: (now - self.played).num_seconds().max(0),
+                skipped,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::{Duration, NaiveDate};
+
+        #[test]
+        fn test_escalation() {
+            let at = NaiveDate::from_ymd_opt(2026, 1, 5)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap();
+            let played = at + Duration::seconds(2);
+            let mut alert = Alert::new(at, played, 300);
+            assert_eq!(alert.stage(), Stage::Sound);
+            assert!(!alert.escalate(played + Duration::seconds(59)));
+
+            let mut stages = Vec::new();
+            for minute in 1..10 {
+                if alert.escalate(played + Duration::minutes(minute)) {
+                    stages.push(alert.stage());
+                }
+            }
+            assert_eq!(stages.len(), MAX_LEVEL);
+            assert_eq!(stages[0], Stage::Notification);
+            assert_eq!(stages[1], Stage::Overlay);
+            assert_eq!(alert.level, MAX_LEVEL);
+
+            //a longer break escalates more slowly
+            let mut long = Alert::new(at, played, 15 * 60);
+            assert!(!long.escalate(played + Duration::minutes(2)));
+            assert!(long.escalate(played + Duration::minutes(3)));
+
+            let response = alert.respond(at + Duration::seconds(95), true);
+            assert_eq!(
+                response,
+                Response {
+                    at,
+                    seconds: 93,
+                    skipped: true
+                }
+            );
+        }
+    }
+}
+
+pub use escalation::*;
@@ -0,0 +1,269 @@
+//! Rules given as a cron expression or an RFC 5545 RRULE, for patterns the
+//! from/to/interval triple can't express e.g. "at :25 and :55 every hour".
+//! Occurrences are limited to the rule's days (all days when empty) and,
+//! when `from` is before `to`, to the hours from:00 through to:00.
+mod expression {
+    use super::super::alarm_utils::get_weekday;
+    use super::super::rrule::RRule;
+    use crate::ui_handler::{Rule, RuleKind};
+
+    use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc, Weekday};
+    use cron::Schedule as CronSchedule;
+    use std::str::FromStr;
+
+    const WEEKDAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+    #[derive(Clone, Debug)]
+    enum Expression {
+        Cron(CronSchedule),
+        //the rule along with its DTSTART, if one was given
+        Rrule(RRule, Option<NaiveDateTime>),
+    }
+
+    /// A parsed cron or RRULE rule
+    #[derive(Clone, Debug)]
+    pub struct ExpressionRule {
+        days: Vec<Weekday>,
+        from: usize,
+        to: usize,
+        expression: Expression,
+    }
+
+    impl ExpressionRule {
+        pub fn new(rule: &Rule) -> Result<Self, String> {
+            let source = rule.expression.as_deref().unwrap_or_default().trim();
+            if source.is_empty() {
+                return Err("Expression is required".to_string());
+            }
+
+            let expression = match rule.kind {
+                RuleKind::Cron => Expression::Cron(parse_cron(source)?),
+                RuleKind::Rrule => parse_rrule(source)?,
+                RuleKind::Interval => return Err("Not an expression rule".to_string()),
+            };
+
+            let days = rule
+                .days
+                .iter()
+                .map(|d| get_weekday(d))
+                .collect::<Result<Vec<Weekday>, String>>()?;
+
+            Ok(Self {
+                days,
+                from: rule.from,
+                to: rule.to,
+                expression,
+            })
+        }
+
+        /// Hours and minutes of the occurrences on a date, sorted
+        pub fn occurrences(&self, date: NaiveDate) -> Vec<(usize, usize)> {
+            if !self.days.is_empty() && !self.days.contains(&date.weekday()) {
+                return Vec::new();
+            }
+
+            let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+            let next_midnight = midnight + Duration::days(1);
+            let (start, end) = if self.from < self.to {
+                let start = midnight + Duration::hours(self.from as i64);
+                let end = midnight + Duration::hours(self.to as i64) + Duration::minutes(1);
+                (start, end.min(next_midnight))
+            } else {
+                (midnight, next_midnight)
+            };
+
+            let times: Vec<NaiveDateTime> = match &self.expression {
+                //cron is evaluated on wall clock time, which UTC stands in for
+                Expression::Cron(schedule) => {
+                    let after = Utc.from_utc_datetime(&(start - Duration::seconds(1)));
+                    schedule
+                        .after(&after)
+                        .map(|t| t.naive_utc())
+                        .take_while(|t| *t < end)
+                        .collect()
+                }
+                //without a DTSTART, the rule starts afresh each day
+                Expression::Rrule(rule, dtstart) => {
+                    rule.between(dtstart.unwrap_or(start), start, end)
+                }
+            };
+
+            times
+                .iter()
+                .map(|t| (t.hour() as usize, t.minute() as usize))
+                .collect()
+        }
+    }
+
+    /// Parses a standard five field expression (minute hour day-of-month
+    /// month day-of-week). Weekdays are numbered 0-7 from Sunday, as in cron
+    fn parse_cron(source: &str) -> Result<CronSchedule, String> {
+        let fields: Vec<&str> = source.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Cron expression must have 5 fields, found {}",
+                fields.len()
+            ));
+        }
+
+        let weekdays = weekday_names(fields[4])?;
+        let expression = format!(
+            "0 {} {} {} {} {}",
+            fields[0], fields[1], fields[2], fields[3], weekdays
+        );
+
+        CronSchedule::from_str(&expression).map_err(|e| format!("Invalid cron expression: {}", e))
+    }
+
+    /// The cron crate numbers weekdays 1-7 from Sunday. Numbers are
+    /// replaced with names so that 0-7 keep their usual meaning
+    fn weekday_names(field: &str) -> Result<String, String> {
+        let name = |v: &str| -> Result<String, String> {
+            match v.parse::<usize>() {
+                Ok(n) if n <= 7 => Ok(WEEKDAY_NAMES[n % 7].to_string()),
+                Ok(n) => Err(format!("Invalid weekday {}", n)),
+                Err(_) => Ok(v.to_string()),
+            }
+        };
+
+        let mut items = Vec::new();
+        for item in field.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+
+            let item = match range.split_once('-') {
+                //a range ending on Sunday wraps around
+                Some((first, "7")) if step.is_none() => format!("{}-Sat,Sun", name(first)?),
+                Some((_, "7")) => return Err(format!("Unsupported weekday range {}", item)),
+                Some((first, last)) => format!("{}-{}", name(first)?, name(last)?),
+                None => name(range)?,
+            };
+
+            items.push(match step {
+                Some(step) => format!("{}/{}", item, step),
+                None => item,
+            });
+        }
+
+        Ok(items.join(","))
+    }
+
+    /// Parses an RRULE, optionally preceded by a line like
+    /// `DTSTART:20260105T091500` giving the (wall clock) start
+    fn parse_rrule(source: &str) -> Result<Expression, String> {
+        let mut dtstart = None;
+        let mut rule = None;
+
+        for line in source.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(value) = line.strip_prefix("DTSTART:") {
+                let t = NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%S")
+                    .map_err(|_| format!("Invalid DTSTART {}", value))?;
+                dtstart = Some(t);
+            } else if rule.is_none() {
+                rule = Some(RRule::parse(line)?);
+            } else {
+                return Err("Only one RRULE is supported".to_string());
+            }
+        }
+
+        match rule {
+            Some(rule) => Ok(Expression::Rrule(rule, dtstart)),
+            None => Err("RRULE is required".to_string()),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn rule(kind: RuleKind, expression: &str, from: usize, to: usize) -> Rule {
+            Rule {
+                serial: 1,
+                from,
+                to,
+                kind,
+                expression: Some(expression.to_string()),
+                ..Default::default()
+            }
+        }
+
+        fn date(d: u32) -> NaiveDate {
+            NaiveDate::from_ymd_opt(2026, 1, d).unwrap()
+        }
+
+        #[test]
+        fn test_cron_minutes() {
+            let r = ExpressionRule::new(&rule(RuleKind::Cron, "25,55 * * * *", 9, 11)).unwrap();
+
+            //the window ends at 11:00
+            assert_eq!(
+                r.occurrences(date(5)),
+                vec![(9, 25), (9, 55), (10, 25), (10, 55)]
+            );
+        }
+
+        #[test]
+        fn test_cron_weekdays() {
+            let workdays =
+                ExpressionRule::new(&rule(RuleKind::Cron, "0 12 * * 1-5", 0, 0)).unwrap();
+            //5 Jan 2026 is a Monday
+            assert_eq!(workdays.occurrences(date(5)), vec![(12, 0)]);
+            assert_eq!(workdays.occurrences(date(9)), vec![(12, 0)]);
+            assert!(workdays.occurrences(date(10)).is_empty());
+            assert!(workdays.occurrences(date(11)).is_empty());
+
+            let weekend = ExpressionRule::new(&rule(RuleKind::Cron, "0 12 * * 6-7", 0, 0)).unwrap();
+            assert!(weekend.occurrences(date(9)).is_empty());
+            assert_eq!(weekend.occurrences(date(10)), vec![(12, 0)]);
+            assert_eq!(weekend.occurrences(date(11)), vec![(12, 0)]);
+
+            let sunday = ExpressionRule::new(&rule(RuleKind::Cron, "30 8 * * 0", 0, 0)).unwrap();
+            assert_eq!(sunday.occurrences(date(11)), vec![(8, 30)]);
+        }
+
+        #[test]
+        fn test_rrule_with_dtstart() {
+            let source = "DTSTART:20260105T091500\nRRULE:FREQ=MINUTELY;INTERVAL=90";
+            let r = ExpressionRule::new(&rule(RuleKind::Rrule, source, 9, 17)).unwrap();
+
+            let expected = vec![(9, 15), (10, 45), (12, 15), (13, 45), (15, 15), (16, 45)];
+            assert_eq!(r.occurrences(date(5)), expected);
+            assert_eq!(r.occurrences(date(7)), expected);
+            assert!(r.occurrences(date(4)).is_empty());
+        }
+
+        #[test]
+        fn test_rrule_without_dtstart() {
+            let mut rrule = rule(RuleKind::Rrule, "FREQ=MINUTELY;INTERVAL=90", 9, 12);
+            rrule.days = vec!["Tue".to_string()];
+            let r = ExpressionRule::new(&rrule).unwrap();
+
+            assert_eq!(r.occurrences(date(6)), vec![(9, 0), (10, 30), (12, 0)]);
+            assert!(r.occurrences(date(7)).is_empty());
+        }
+
+        #[test]
+        fn test_invalid_expressions() {
+            let invalid = [
+                rule(RuleKind::Cron, "", 0, 0),
+                rule(RuleKind::Cron, "0 0 12 * * *", 0, 0),
+                rule(RuleKind::Cron, "61 * * * *", 0, 0),
+                rule(RuleKind::Cron, "0 12 * * 8", 0, 0),
+                rule(RuleKind::Rrule, "FREQ=YEARLY", 0, 0),
+                rule(RuleKind::Rrule, "DTSTART:2026\nFREQ=DAILY", 0, 0),
+                Rule {
+                    days: vec!["Someday".to_string()],
+                    ..rule(RuleKind::Cron, "* * * * *", 0, 0)
+                },
+            ];
+
+            for r in &invalid {
+                assert!(ExpressionRule::new(r).is_err(), "{:?}", r.expression);
+            }
+        }
+    }
+}
+
+pub use expression::*;
@@ -0,0 +1,115 @@
+//! Focus sessions for deep work. Breaks of the rules falling in a session
+//! are held back and, when it ends, a single longer break makes up for
+//! them. Break timers are set on purpose and still go off. Saved so a
+//! session outlives a restart
+mod focus {
+    use super::super::alarm_utils::BREAK_MINUTES;
+
+    use chrono::{Duration, NaiveDateTime};
+    use serde::{Deserialize, Serialize};
+
+    //the break ending a session makes up for this many breaks at most
+    const MAX_DEFERRED_BREAKS: usize = 3;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Focus {
+        pub started: NaiveDateTime,
+        pub ends_at: NaiveDateTime,
+        //breaks held back so far
+        #[serde(default)]
+        pub deferred: Vec<NaiveDateTime>,
+    }
+
+    /// What the UI is told about the session
+    #[derive(Clone, Debug, Default, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct FocusStatus {
+        pub active: bool,
+        pub ends_at: Option<NaiveDateTime>,
+        //seconds
+        pub remaining: i64,
+        pub deferred: usize,
+    }
+
+    impl Focus {
+        pub fn new(now: NaiveDateTime, minutes: usize) -> Self {
+            Self {
+                started: now,
+                ends_at: now + Duration::minutes(minutes as i64),
+                deferred: Vec::new(),
+            }
+        }
+
+        pub fn extend(&mut self, minutes: usize) {
+            self.ends_at += Duration::minutes(minutes as i64);
+        }
+
+        pub fn is_over(&self, now: NaiveDateTime) -> bool {
+            self.ends_at <= now
+        }
+
+        pub fn defer(&mut self, at: NaiveDateTime) {
+            self.deferred.push(at);
+        }
+
+        /// Length of the break ending the session, BREAK_MINUTES for each
+        /// break held back. None when there were none
+        pub fn break_minutes(&self) -> Option<i64> {
+            match self.deferred.len() {
+                0 => None,
+                n => Some(BREAK_MINUTES * n.min(MAX_DEFERRED_BREAKS) as i64),
+            }
+        }
+
+        pub fn status(&self, now: NaiveDateTime) -> FocusStatus {
+            FocusStatus {
+                active: true,
+                ends_at: Some(self.ends_at),
+                remaining: (self.ends_at - now).num_seconds().max(0),
+                deferred: self.deferred.len(),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::NaiveDate;
+
+        fn at(h: u32, m: u32) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 1, 5)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        }
+
+        #[test]
+        fn test_focus() {
+            let mut focus = Focus::new(at(9, 0), 90);
+            assert!(!focus.is_over(at(10, 29)));
+            assert_eq!(focus.break_minutes(), None);
+
+            focus.extend(30);
+            assert!(!focus.is_over(at(10, 30)));
+            assert!(focus.is_over(at(11, 0)));
+
+            focus.defer(at(9, 30));
+            focus.defer(at(10, 30));
+            let status = focus.status(at(10, 45));
+            assert_eq!(status.remaining, 15 * 60);
+            assert_eq!(status.deferred, 2);
+            assert_eq!(focus.break_minutes(), Some(2 * BREAK_MINUTES));
+
+            for _ in 0..5 {
+                focus.defer(at(10, 50));
+            }
+            assert_eq!(
+                focus.break_minutes(),
+                Some(MAX_DEFERRED_BREAKS as i64 * BREAK_MINUTES)
+            );
+        }
+    }
+}
+
+pub use focus::*;
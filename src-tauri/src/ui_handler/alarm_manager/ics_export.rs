@@ -0,0 +1,303 @@
+//! Exports the break schedule as an iCalendar file so it can be overlaid
+//! on other calendars. Each rule becomes one weekly VEVENT per weekday.
+//! RRULE expands to every combination of BYHOUR and BYMINUTE, so minutes
+//! which occur in different hours of a day get VEVENTs of their own.
+//! Only valid weekly break rules without randomisation or ramps are
+//! exported, the others are reported as left out.
+mod ics_export {
+    use super::super::alarm_utils::*;
+    use crate::ui_handler::{Category, Recurrence, Rule, RuleKind};
+
+    use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    /// A rule which could not be exported
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct LeftOut {
+        pub serial: usize,
+        pub reason: String,
+    }
+
+    /// Builds the calendar, along with the rules left out of it. Events
+    /// start on the first matching day on or after `start` and use
+    /// floating (wall clock) time
+    pub fn export_ics(
+        rules: &[Rule],
+        start: NaiveDate,
+        stamp: DateTime<Utc>,
+    ) -> (String, Vec<LeftOut>) {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//68kilo//Take a break//EN".to_string(),
+        ];
+
+        let mut left_out = Vec::new();
+        let mut fixed = Vec::new();
+        for rule in rules {
+            match unexportable(rule) {
+                Some(reason) => left_out.push(LeftOut {
+                    serial: rule.serial,
+                    reason,
+                }),
+                //rules which have ended are not worth reporting
+                None if rule.valid_until.is_some_and(|until| until < start) => (),
+                None => fixed.push(rule),
+            }
+        }
+
+        for rule in fixed {
+            let start = rule.valid_from.map_or(start, |from| from.max(start));
+            let until = rule
+                .valid_until
+                .map(|until| format!(";UNTIL={}T235959", until.format("%Y%m%d")))
+                .unwrap_or_default();
+
+            for (weekday, hour_map) in get_alarms(std::slice::from_ref(rule)) {
+                //hours at which each minute occurs
+                let mut minute_hours: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+                for (hour, mins) in hour_map {
+                    for min in mins {
+                        minute_hours.entry(min).or_default().push(hour);
+                    }
+                }
+
+                //minutes occurring in the same hours go in one event
+                let mut groups: BTreeMap<Vec<usize>, Vec<usize>> = BTreeMap::new();
+                for (min, mut hours) in minute_hours {
+                    hours.sort();
+                    groups.entry(hours).or_default().push(min);
+                }
+
+                let mut groups: Vec<(Vec<usize>, Vec<usize>)> = groups.into_iter().collect();
+                groups.sort_by_key(|(hours, mins)| (hours[0], mins[0]));
+
+                let date = first_on_or_after(start, weekday);
+                for (n, (hours, mins)) in groups.iter().enumerate() {
+                    let dtstart = date
+                        .and_hms_opt(hours[0] as u32, mins[0] as u32, 0)
+                        .unwrap();
+
+                    lines.push("BEGIN:VEVENT".to_string());
+                    lines.push(format!(
+                        "UID:rule-{}-{}-{}@take-a-break",
+                        rule.serial,
+                        ical_day(weekday),
+                        n
+                    ));
+                    lines.push(format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+                    lines.push(format!("DTSTART:{}", dtstart.format("%Y%m%dT%H%M%S")));
+                    lines.push(format!("DURATION:PT{}M", BREAK_MINUTES));
+                    lines.push(format!(
+                        "RRULE:FREQ=WEEKLY;BYDAY={};BYHOUR={};BYMINUTE={}{}",
+                        ical_day(weekday),
+                        join(hours),
+                        join(mins),
+                        until
+                    ));
+                    lines.push("SUMMARY:Take a break".to_string());
+                    lines.push("TRANSP:TRANSPARENT".to_string());
+                    lines.push("END:VEVENT".to_string());
+                }
+            }
+        }
+
+        lines.push("END:VCALENDAR".to_string());
+        (lines.join("\r\n") + "\r\n", left_out)
+    }
+
+    /// Why a rule can't be exported as fixed weekly events, if it can't
+    fn unexportable(rule: &Rule) -> Option<String> {
+        if let Err(e) = validate_rule(rule) {
+            return Some(e);
+        }
+
+        let reason = if rule.kind != RuleKind::Interval {
+            "Only interval rules can be exported"
+        } else if rule.category != Category::Break {
+            "Only break rules can be exported"
+        } else if rule.random.is_some() {
+            "Randomised rules can't be exported"
+        } else if rule.ramp.is_some() {
+            "Ramped rules can't be exported"
+        } else if rule.recurrence != Recurrence::Week {
+            "Only weekly rules can be exported"
+        } else {
+            return None;
+        };
+
+        Some(reason.to_string())
+    }
+
+    fn first_on_or_after(date: NaiveDate, weekday: Weekday) -> NaiveDate {
+        let days = (7 + weekday.num_days_from_monday() as i64
+            - date.weekday().num_days_from_monday() as i64)
+            % 7;
+        date + Duration::days(days)
+    }
+
+    fn ical_day(weekday: Weekday) -> &'static str {
+        match weekday {
+            Weekday::Mon => "MO",
+            Weekday::Tue => "TU",
+            Weekday::Wed => "WE",
+            Weekday::Thu => "TH",
+            Weekday::Fri => "FR",
+            Weekday::Sat => "SA",
+            Weekday::Sun => "SU",
+        }
+    }
+
+    fn join(v: &[usize]) -> String {
+        v.iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::super::calendar::*;
+        use super::super::super::simulator::*;
+        use super::*;
+        use crate::ui_handler::Settings;
+        use chrono::{NaiveDateTime, TimeZone, Timelike};
+        use ical::IcalParser;
+        use std::io::BufReader;
+
+        fn rules() -> Vec<Rule> {
+            vec![
+                Rule {
+                    serial: 1,
+                    days: vec!["Mon".to_string(), "Wed".to_string()],
+                    interval: 30,
+                    from: 9,
+                    to: 11,
+                    ..Default::default()
+                },
+                Rule {
+                    serial: 2,
+                    days: vec!["Fri".to_string()],
+                    interval: 20,
+                    from: 14,
+                    to: 15,
+                    ..Default::default()
+                },
+            ]
+        }
+
+        #[test]
+        fn test_export_rrules() {
+            let start = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+            let (ics, left_out) = export_ics(&rules(), start, Utc::now());
+            assert!(left_out.is_empty());
+
+            let calendar = IcalParser::new(BufReader::new(ics.as_bytes()))
+                .next()
+                .unwrap()
+                .unwrap();
+
+            let mut rrules: Vec<(String, String)> = calendar
+                .events
+                .iter()
+                .map(|e| {
+                    let value = |name: &str| {
+                        e.properties
+                            .iter()
+                            .find(|p| p.name == name)
+                            .and_then(|p| p.value.clone())
+                            .unwrap()
+                    };
+                    (value("DTSTART"), value("RRULE"))
+                })
+                .collect();
+            rrules.sort();
+
+            assert_eq!(
+                rrules,
+                vec![
+                    (
+                        "20260107T093000".to_string(),
+                        "FREQ=WEEKLY;BYDAY=WE;BYHOUR=9,10;BYMINUTE=30".to_string()
+                    ),
+                    (
+                        "20260107T100000".to_string(),
+                        "FREQ=WEEKLY;BYDAY=WE;BYHOUR=10,11;BYMINUTE=0".to_string()
+                    ),
+                    (
+                        "20260109T142000".to_string(),
+                        "FREQ=WEEKLY;BYDAY=FR;BYHOUR=14;BYMINUTE=20,40".to_string()
+                    ),
+                    (
+                        "20260109T150000".to_string(),
+                        "FREQ=WEEKLY;BYDAY=FR;BYHOUR=15;BYMINUTE=0".to_string()
+                    ),
+                    (
+                        "20260112T093000".to_string(),
+                        "FREQ=WEEKLY;BYDAY=MO;BYHOUR=9,10;BYMINUTE=30".to_string()
+                    ),
+                    (
+                        "20260112T100000".to_string(),
+                        "FREQ=WEEKLY;BYDAY=MO;BYHOUR=10,11;BYMINUTE=0".to_string()
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_export_round_trip() {
+            let start = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+            let (ics, _) = export_ics(&rules(), start, Utc::now());
+
+            //exported events are transparent, so read them back as busy
+            let ics = ics.replace("TRANSP:TRANSPARENT", "TRANSP:OPAQUE");
+            let from = start.and_hms_opt(0, 0, 0).unwrap();
+            let to = from + Duration::days(14);
+            let exported: Vec<NaiveDateTime> = parse_busy_blocks(&ics, &Utc, from, to)
+                .iter()
+                .map(|b| b.from)
+                .collect();
+
+            let schedule = Schedule::for_category(
+                &Settings {
+                    rules: rules(),
+                    ..Default::default()
+                },
+                &Category::Break,
+            );
+            let simulated: Vec<NaiveDateTime> = simulate(
+                &schedule,
+                Utc.from_utc_datetime(&from),
+                Utc.from_utc_datetime(&to),
+                &[],
+            )
+            .iter()
+            .map(|e| e.at.naive_local())
+            .collect();
+
+            assert_eq!(exported.len(), 2 * (4 + 4 + 3));
+            assert_eq!(exported, simulated);
+            assert!(exported.iter().all(|t| t.second() == 0));
+        }
+
+        #[test]
+        fn test_export_left_out() {
+            let mut rules = rules();
+            rules[0].days.push("Someday".to_string());
+            rules[1].category = Category::Water;
+
+            let start = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+            let (ics, left_out) = export_ics(&rules, start, Utc::now());
+            assert!(!ics.contains("BEGIN:VEVENT"));
+            assert_eq!(
+                left_out.iter().map(|l| l.serial).collect::<Vec<_>>(),
+                vec![1, 2]
+            );
+            assert_eq!(left_out[1].reason, "Only break rules can be exported");
+        }
+    }
+}
+
+pub use ics_export::*;
@@ -0,0 +1,267 @@
+//! Micro-breaks driven by how much the keyboard and mouse are used, not only
+//! by time. A monitor counts keystrokes and mouse movement, and the counts of
+//! the last few minutes are checked against thresholds. Reading input devices
+//! is only supported on Linux, through evdev
+mod intensity {
+    use crate::ui_handler::IntensitySettings;
+
+    use chrono::{Duration, NaiveDateTime};
+    use std::collections::VecDeque;
+    use std::ops::AddAssign;
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct InputCounts {
+        pub keys: u64,
+        //distance the pointer moved, in device units
+        pub mouse: u64,
+    }
+
+    impl AddAssign for InputCounts {
+        fn add_assign(&mut self, other: Self) {
+            self.keys += other.keys;
+            self.mouse += other.mouse;
+        }
+    }
+
+    pub trait InputMonitor: Send {
+        /// Keystrokes and mouse movement since the previous call
+        fn take(&mut self) -> InputCounts;
+    }
+
+    /// Counts of the minutes in the sliding window
+    #[derive(Debug, Default)]
+    pub struct Intensity {
+        window: VecDeque<(NaiveDateTime, InputCounts)>,
+    }
+
+    impl Intensity {
+        /// Adds the counts of the minute ending at `at`. Returns true when
+        /// the window reaches a threshold, after which it starts afresh
+        pub fn add(
+            &mut self,
+            at: NaiveDateTime,
+            counts: InputCounts,
+            settings: &IntensitySettings,
+        ) -> bool {
+            let start = at - Duration::minutes(settings.window.max(1) as i64);
+            self.window.retain(|(t, _)| *t > start);
+            self.window.push_back((at, counts));
+
+            let mut total = InputCounts::default();
+            for (_, c) in &self.window {
+                total += *c;
+            }
+
+            //a threshold of 0 is not checked
+            let reached = (settings.keystrokes > 0 && total.keys >= settings.keystrokes)
+                || (settings.mouse > 0 && total.mouse >= settings.mouse);
+            if reached {
+                self.window.clear();
+            }
+
+            reached
+        }
+    }
+
+    /// Monitor of the platform. Fails if there is none or it can't read
+    /// any device
+    pub fn start_monitor() -> Result<Box<dyn InputMonitor>, String> {
+        #[cfg(target_os = "linux")]
+        {
+            evdev_monitor::EvdevMonitor::start().map(|m| Box::new(m) as Box<dyn InputMonitor>)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err("Input monitoring is not supported on this platform".to_string())
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    mod evdev_monitor {
+        use super::{InputCounts, InputMonitor};
+
+        use evdev::{EventSummary, KeyCode, RelativeAxisCode};
+        use log::debug;
+        use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+        use std::sync::Arc;
+
+        /// Reads every keyboard and mouse under /dev/input on its own
+        /// thread. Needs read access to the devices, usually through
+        /// membership of the `input` group
+        pub struct EvdevMonitor {
+            keys: Arc<AtomicU64>,
+            mouse: Arc<AtomicU64>,
+            stop: Arc<AtomicBool>,
+        }
+
+        impl EvdevMonitor {
+            /// Fails if no keyboard or mouse could be opened
+            pub fn start() -> Result<Self, String> {
+                let monitor = Self {
+                    keys: Arc::new(AtomicU64::new(0)),
+                    mouse: Arc::new(AtomicU64::new(0)),
+                    stop: Arc::new(AtomicBool::new(false)),
+                };
+
+                //devices which can't be opened are left out by enumerate
+                let mut opened = 0;
+                for (path, mut device) in evdev::enumerate() {
+                    let keyboard = device
+                        .supported_keys()
+                        .is_some_and(|k| k.contains(KeyCode::KEY_A));
+                    let mouse = device
+                        .supported_relative_axes()
+                        .is_some_and(|r| r.contains(RelativeAxisCode::REL_X));
+                    if !keyboard && !mouse {
+                        continue;
+                    }
+
+                    debug!("intensity: reading {}", path.display());
+                    opened += 1;
+                    let keys = monitor.keys.clone();
+                    let moved = monitor.mouse.clone();
+                    let stop = monitor.stop.clone();
+
+                    std::thread::spawn(move || {
+                        //blocks until the device sends something, so stopping
+                        //takes effect on the next event
+                        while !stop.load(Ordering::Relaxed) {
+                            let events = match device.fetch_events() {
+                                Ok(events) => events,
+                                Err(e) => {
+                                    debug!("intensity: {} stopped: {}", path.display(), e);
+                                    return;
+                                }
+                            };
+
+                            for event in events {
+                                match event.destructure() {
+                                    //presses only, leaving out mouse buttons
+                                    EventSummary::Key(_, code, 1) if code < KeyCode::BTN_0 => {
+                                        keys.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                    EventSummary::RelativeAxis(_, code, value)
+                                        if code == RelativeAxisCode::REL_X
+                                            || code == RelativeAxisCode::REL_Y =>
+                                    {
+                                        moved.fetch_add(
+                                            value.unsigned_abs() as u64,
+                                            Ordering::Relaxed,
+                                        );
+                                    }
+                                    _ => (),
+                                }
+                            }
+                        }
+                    });
+                }
+
+                if opened == 0 {
+                    return Err(
+                        "No keyboard or mouse could be read. Is the user in the input group?"
+                            .to_string(),
+                    );
+                }
+
+                Ok(monitor)
+            }
+        }
+
+        impl InputMonitor for EvdevMonitor {
+            fn take(&mut self) -> InputCounts {
+                InputCounts {
+                    keys: self.keys.swap(0, Ordering::Relaxed),
+                    mouse: self.mouse.swap(0, Ordering::Relaxed),
+                }
+            }
+        }
+
+        impl Drop for EvdevMonitor {
+            fn drop(&mut self) {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Plays back counts given to it, one set per call
+    #[cfg(test)]
+    #[derive(Default)]
+    pub struct FakeMonitor {
+        pub counts: VecDeque<InputCounts>,
+    }
+
+    #[cfg(test)]
+    impl InputMonitor for FakeMonitor {
+        fn take(&mut self) -> InputCounts {
+            self.counts.pop_front().unwrap_or_default()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::NaiveDate;
+
+        fn at(m: u32) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 1, 5)
+                .unwrap()
+                .and_hms_opt(10, m, 0)
+                .unwrap()
+        }
+
+        fn counts(keys: u64, mouse: u64) -> InputCounts {
+            InputCounts { keys, mouse }
+        }
+
+        /// Minutes at which micro-breaks are triggered
+        fn triggered(monitor: &mut dyn InputMonitor, settings: &IntensitySettings) -> Vec<u32> {
+            let mut intensity = Intensity::default();
+            (0..30)
+                .filter(|m| intensity.add(at(*m), monitor.take(), settings))
+                .collect()
+        }
+
+        #[test]
+        fn test_keystrokes() {
+            let settings = IntensitySettings {
+                enabled: true,
+                window: 5,
+                keystrokes: 1000,
+                mouse: 0,
+            };
+
+            //steady typing of 250 a minute reaches 1000 in the 4th minute
+            let mut monitor = FakeMonitor {
+                counts: std::iter::repeat(counts(250, 1_000_000)).take(10).collect(),
+            };
+            assert_eq!(triggered(&mut monitor, &settings), vec![3, 7]);
+
+            //bursts further apart than the window never add up
+            let mut monitor = FakeMonitor {
+                counts: [600, 0, 0, 0, 0, 0, 600, 0, 0, 0, 0, 0, 600]
+                    .iter()
+                    .map(|k| counts(*k, 0))
+                    .collect(),
+            };
+            assert!(triggered(&mut monitor, &settings).is_empty());
+        }
+
+        #[test]
+        fn test_mouse() {
+            let settings = IntensitySettings {
+                enabled: true,
+                window: 10,
+                keystrokes: 1000,
+                mouse: 5000,
+            };
+
+            let mut monitor = FakeMonitor {
+                counts: [counts(10, 3000), counts(10, 1000), counts(10, 1000)].into(),
+            };
+            assert_eq!(triggered(&mut monitor, &settings), vec![2]);
+        }
+    }
+}
+
+pub use intensity::*;
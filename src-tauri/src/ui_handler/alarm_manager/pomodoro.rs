@@ -0,0 +1,206 @@
+//! Pomodoro timer. Unlike the rules, which are matched against the clock,
+//! this keeps state: the current phase, the work blocks done since the last
+//! long break and when the phase ends. The state is plain data so it can
+//! be saved and picked up again after a restart.
+mod pomodoro {
+    use crate::ui_handler::{Phase, PomodoroSettings};
+
+    use chrono::{DateTime, Duration, Utc};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Pomodoro {
+        pub phase: Phase,
+        //work blocks completed since the last long break
+        pub cycle: usize,
+        //end of the phase while running
+        pub ends_at: Option<DateTime<Utc>>,
+        //seconds left in the phase when stopped part way
+        pub remaining: Option<i64>,
+    }
+
+    /// What the UI is told about the timer
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct PomodoroStatus {
+        pub phase: Phase,
+        pub cycle: usize,
+        pub cycles: usize,
+        pub running: bool,
+        //seconds
+        pub remaining: i64,
+    }
+
+    impl Pomodoro {
+        pub fn is_running(&self) -> bool {
+            self.ends_at.is_some()
+        }
+
+        /// Starts the current phase, or resumes it if it was stopped part way
+        pub fn start(&mut self, settings: &PomodoroSettings, now: DateTime<Utc>) {
+            if self.is_running() {
+                return;
+            }
+
+            let left = match self.remaining.take() {
+                Some(secs) => Duration::seconds(secs),
+                None => length(&self.phase, settings),
+            };
+            self.ends_at = Some(now + left);
+        }
+
+        /// Stops the timer, keeping the time left in the phase
+        pub fn stop(&mut self, now: DateTime<Utc>) {
+            if let Some(ends_at) = self.ends_at.take() {
+                self.remaining = Some((ends_at - now).num_seconds().max(0));
+            }
+        }
+
+        /// Back to the start of the first work block, stopped
+        pub fn reset(&mut self) {
+            *self = Self::default();
+        }
+
+        /// Moves on to the next phase if the current one has ended. Returns
+        /// the phase moved to. The next phase starts when the previous one
+        /// ended, unless that is already past e.g. after the app was closed
+        pub fn tick(&mut self, settings: &PomodoroSettings, now: DateTime<Utc>) -> Option<Phase> {
+            let ends_at = self.ends_at.filter(|e| *e <= now)?;
+
+            self.phase = match self.phase {
+                Phase::Work => {
+                    self.cycle += 1;
+                    if self.cycle >= settings.cycles.max(1) {
+                        Phase::LongBreak
+                    } else {
+                        Phase::ShortBreak
+                    }
+                }
+                Phase::ShortBreak => Phase::Work,
+                Phase::LongBreak => {
+                    self.cycle = 0;
+                    Phase::Work
+                }
+            };
+
+            let next = ends_at + length(&self.phase, settings);
+            self.ends_at = Some(if next > now {
+                next
+            } else {
+                now + length(&self.phase, settings)
+            });
+
+            Some(self.phase.clone())
+        }
+
+        /// Time until the phase ends, if running
+        pub fn until_end(&self, now: DateTime<Utc>) -> Option<std::time::Duration> {
+            self.ends_at
+                .map(|e| (e - now).to_std().unwrap_or(std::time::Duration::ZERO))
+        }
+
+        pub fn status(&self, settings: &PomodoroSettings, now: DateTime<Utc>) -> PomodoroStatus {
+            let remaining = match (self.ends_at, self.remaining) {
+                (Some(ends_at), _) => (ends_at - now).num_seconds().max(0),
+                (None, Some(secs)) => secs,
+                (None, None) => length(&self.phase, settings).num_seconds(),
+            };
+
+            PomodoroStatus {
+                phase: self.phase.clone(),
+                cycle: self.cycle,
+                cycles: settings.cycles,
+                running: self.is_running(),
+                remaining,
+            }
+        }
+    }
+
+    fn length(phase: &Phase, settings: &PomodoroSettings) -> Duration {
+        let minutes = match phase {
+            Phase::Work => settings.work,
+            Phase::ShortBreak => settings.short_break,
+            Phase::LongBreak => settings.long_break,
+        };
+
+        Duration::minutes(minutes.max(1) as i64)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::TimeZone;
+
+        fn at(min: i64) -> DateTime<Utc> {
+            Utc.with_ymd_and_hms(2026, 1, 5, 9, 0, 0).unwrap() + Duration::minutes(min)
+        }
+
+        fn settings() -> PomodoroSettings {
+            PomodoroSettings {
+                work: 25,
+                short_break: 5,
+                long_break: 15,
+                cycles: 2,
+            }
+        }
+
+        #[test]
+        fn test_cycles() {
+            let s = settings();
+            let mut p = Pomodoro::default();
+            assert_eq!(p.tick(&s, at(0)), None);
+
+            p.start(&s, at(0));
+            assert_eq!(p.tick(&s, at(24)), None);
+            assert_eq!(p.tick(&s, at(25)), Some(Phase::ShortBreak));
+            assert_eq!(p.tick(&s, at(30)), Some(Phase::Work));
+            assert_eq!(p.tick(&s, at(55)), Some(Phase::LongBreak));
+            assert_eq!(p.cycle, 2);
+            assert_eq!(p.tick(&s, at(70)), Some(Phase::Work));
+            assert_eq!(p.cycle, 0);
+
+            //phases follow on from when the last one ended, not from the tick
+            assert_eq!(
+                p.tick(&s, at(95) + Duration::seconds(40)),
+                Some(Phase::ShortBreak)
+            );
+            assert_eq!(p.ends_at, Some(at(100)));
+        }
+
+        #[test]
+        fn test_stop_and_resume() {
+            let s = settings();
+            let mut p = Pomodoro::default();
+            assert_eq!(p.status(&s, at(0)).remaining, 25 * 60);
+
+            p.start(&s, at(0));
+            p.stop(at(10));
+            assert!(!p.is_running());
+            assert_eq!(p.tick(&s, at(30)), None);
+            assert_eq!(p.status(&s, at(30)).remaining, 15 * 60);
+
+            p.start(&s, at(30));
+            assert_eq!(p.ends_at, Some(at(45)));
+
+            p.reset();
+            assert_eq!(p, Pomodoro::default());
+        }
+
+        #[test]
+        fn test_restart() {
+            let s = settings();
+            let mut p = Pomodoro::default();
+            p.start(&s, at(0));
+
+            let saved = serde_json::to_string(&p).unwrap();
+            let mut p: Pomodoro = serde_json::from_str(&saved).unwrap();
+
+            //the app was closed past the end of the work block
+            assert_eq!(p.tick(&s, at(120)), Some(Phase::ShortBreak));
+            assert_eq!(p.ends_at, Some(at(125)));
+        }
+    }
+}
+
+pub use pomodoro::*;
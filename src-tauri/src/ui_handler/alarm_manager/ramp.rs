@@ -0,0 +1,194 @@
+//! Interval rules whose interval changes through the day, either from set
+//! times of day or as breaks get skipped. Alarms are walked from the start
+//! of the window, each one an interval after the previous, so a change only
+//! moves the alarms after it
+mod ramp {
+    use super::super::alarm_utils::get_weekday;
+    use crate::ui_handler::{Ramp, Rule};
+
+    use chrono::{Datelike, NaiveDate, Timelike, Weekday};
+
+    /// Hours and minutes of a ramped rule's alarms on a date. `skipped` are
+    /// the minutes of the day at which breaks were skipped
+    pub fn ramp_times(rule: &Rule, date: NaiveDate, skipped: &[usize]) -> Vec<(usize, usize)> {
+        let ramp = match &rule.ramp {
+            Some(ramp) => ramp,
+            None => return Vec::new(),
+        };
+
+        let last = 24 * 60 - 1;
+        let start = rule.from * 60 + rule.anchor.offset;
+        let end = rule.to * 60 + rule.anchor.offset;
+        let weekday = date.weekday();
+
+        let mut times = Vec::new();
+        let mut t = if rule.anchor.include_start {
+            start
+        } else {
+            start + interval_at(rule, ramp, weekday, start, skipped)
+        };
+
+        while t <= end.min(last) {
+            if rule.anchor.include_end || t != end {
+                times.push((t / 60, t % 60));
+            }
+            t += interval_at(rule, ramp, weekday, t, skipped);
+        }
+
+        times
+    }
+
+    /// Checks the ramp of an interval rule
+    pub fn validate_ramp(rule: &Rule) -> Result<(), String> {
+        let ramp = match &rule.ramp {
+            Some(ramp) => ramp,
+            None => return Ok(()),
+        };
+
+        if rule.random.is_some() {
+            return Err("Randomised rules can not be ramped".to_string());
+        }
+
+        for step in &ramp.steps {
+            if step.interval == 0 {
+                return Err(format!(
+                    "Interval from {} must be at least a minute",
+                    step.from
+                ));
+            }
+
+            for d in &step.days {
+                get_weekday(d)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Interval following an alarm at minute `t`. The latest step started by
+    /// `t` wins over the rule's interval, then skipped breaks up to `t`
+    /// shorten it
+    fn interval_at(
+        rule: &Rule,
+        ramp: &Ramp,
+        weekday: Weekday,
+        t: usize,
+        skipped: &[usize],
+    ) -> usize {
+        let interval = ramp
+            .steps
+            .iter()
+            .filter(|s| s.days.is_empty() || s.days.iter().any(|d| get_weekday(d) == Ok(weekday)))
+            .map(|s| (s.from.num_seconds_from_midnight() as usize / 60, s.interval))
+            .filter(|(from, _)| *from <= t)
+            .max_by_key(|(from, _)| *from)
+            .map_or(rule.interval, |(_, interval)| interval);
+
+        let skips = skipped.iter().filter(|s| **s <= t).count();
+        let shortened = interval.saturating_sub(skips * ramp.per_skip);
+
+        //never lengthened by the minimum, nor shorter than a minute
+        shortened.max(ramp.min_interval.min(interval)).max(1)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::ui_handler::RampStep;
+        use chrono::NaiveTime;
+
+        fn rule(ramp: Ramp) -> Rule {
+            Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Tue".to_string()],
+                interval: 60,
+                from: 9,
+                to: 18,
+                ramp: Some(ramp),
+                ..Default::default()
+            }
+        }
+
+        fn step(hour: u32, interval: usize, days: &[&str]) -> RampStep {
+            RampStep {
+                from: NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+                interval,
+                days: days.iter().map(|d| d.to_string()).collect(),
+            }
+        }
+
+        fn date(d: u32) -> NaiveDate {
+            NaiveDate::from_ymd_opt(2026, 1, d).unwrap()
+        }
+
+        #[test]
+        fn test_steps() {
+            let r = rule(Ramp {
+                steps: vec![step(15, 40, &[]), step(17, 10, &["Tue"])],
+                ..Default::default()
+            });
+
+            let monday = vec![
+                (10, 0),
+                (11, 0),
+                (12, 0),
+                (13, 0),
+                (14, 0),
+                (15, 0),
+                (15, 40),
+                (16, 20),
+                (17, 0),
+                (17, 40),
+            ];
+            assert_eq!(ramp_times(&r, date(5), &[]), monday);
+
+            //the Tuesday step takes over at 17:00
+            let tuesday = ramp_times(&r, date(6), &[]);
+            assert_eq!(&tuesday[..9], &monday[..9]);
+            assert_eq!(
+                &tuesday[9..],
+                &[(17, 10), (17, 20), (17, 30), (17, 40), (17, 50), (18, 0)]
+            );
+        }
+
+        #[test]
+        fn test_skips() {
+            let r = rule(Ramp {
+                per_skip: 15,
+                min_interval: 20,
+                ..Default::default()
+            });
+
+            //no skips, plain hourly breaks
+            assert_eq!(ramp_times(&r, date(5), &[]).len(), 9);
+
+            //the 10:00 break was skipped, so breaks come every 45 minutes
+            //and then no less than 20 minutes apart after two more skips
+            let times = ramp_times(&r, date(5), &[10 * 60]);
+            assert_eq!(&times[..3], &[(10, 0), (10, 45), (11, 30)]);
+
+            let times = ramp_times(&r, date(5), &[10 * 60, 10 * 60 + 45, 11 * 60 + 15]);
+            assert_eq!(
+                &times[..5],
+                &[(10, 0), (10, 45), (11, 15), (11, 35), (11, 55)]
+            );
+        }
+
+        #[test]
+        fn test_validate_ramp() {
+            assert!(validate_ramp(&rule(Ramp::default())).is_ok());
+            assert!(validate_ramp(&rule(Ramp {
+                steps: vec![step(15, 0, &[])],
+                ..Default::default()
+            }))
+            .is_err());
+            assert!(validate_ramp(&rule(Ramp {
+                steps: vec![step(15, 30, &["Someday"])],
+                ..Default::default()
+            }))
+            .is_err());
+        }
+    }
+}
+
+pub use ramp::*;
@@ -0,0 +1,203 @@
+//! Randomised alarms for interval rules, so that breaks don't fall at the
+//! same predictable times every day. The generator is seeded with the date
+//! and the rule's serial, so a day's alarms are the same across restarts.
+mod random {
+    use super::super::alarm_utils::{get_weekday, interval_times};
+    use crate::ui_handler::{Random, Rule};
+
+    use chrono::{Datelike, NaiveDate};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    /// Hours and minutes of a randomised rule's alarms on a date, sorted
+    pub fn random_times(rule: &Rule, date: NaiveDate) -> Vec<(usize, usize)> {
+        let on_day = rule
+            .days
+            .iter()
+            .any(|d| get_weekday(d) == Ok(date.weekday()));
+
+        let random = match &rule.random {
+            Some(random) if on_day => random,
+            _ => return Vec::new(),
+        };
+
+        let (start, end) = window(rule);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed(rule, date));
+
+        let mut times: Vec<usize> = match random {
+            Random::Jitter { minutes } => {
+                let n = *minutes as i64;
+                interval_times(rule)
+                    .iter()
+                    .map(|(h, m)| {
+                        let t = (h * 60 + m) as i64 + rng.gen_range(-n..=n);
+                        t.clamp(start as i64, end as i64) as usize
+                    })
+                    .collect()
+            }
+            Random::Breaks { count, min_gap } => {
+                // Pick offsets within the slack left after reserving the
+                // gaps, then add the gaps back in
+                let reserved = match reserved(*count, *min_gap) {
+                    Some(r) if *count > 0 && r <= end - start => r,
+                    _ => return Vec::new(),
+                };
+
+                let slack = end - start - reserved;
+                let mut offsets: Vec<usize> =
+                    (0..*count).map(|_| rng.gen_range(0..=slack)).collect();
+                offsets.sort();
+
+                offsets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, o)| start + o + i * min_gap)
+                    .collect()
+            }
+        };
+
+        times.sort();
+        times.dedup();
+        times.iter().map(|t| (t / 60, t % 60)).collect()
+    }
+
+    /// Checks the random options of an interval rule
+    pub fn validate_random(rule: &Rule) -> Result<(), String> {
+        match &rule.random {
+            Some(Random::Breaks { count, min_gap }) => {
+                let (start, end) = window(rule);
+                if *count == 0 {
+                    return Err("At least one break is required".to_string());
+                }
+
+                if !reserved(*count, *min_gap).is_some_and(|r| r <= end.saturating_sub(start)) {
+                    return Err(format!(
+                        "{} breaks {} minutes apart do not fit between {} and {}",
+                        count, min_gap, rule.from, rule.to
+                    ));
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Minutes taken up by the gaps between `count` breaks. None if too
+    /// large to count
+    fn reserved(count: usize, min_gap: usize) -> Option<usize> {
+        count.saturating_sub(1).checked_mul(min_gap)
+    }
+
+    /// Start and end of the rule's window in minutes since midnight
+    fn window(rule: &Rule) -> (usize, usize) {
+        let last = 24 * 60 - 1;
+        let start = (rule.from * 60 + rule.anchor.offset).min(last);
+        let end = (rule.to * 60 + rule.anchor.offset).min(last);
+        (start, end.max(start))
+    }
+
+    //DefaultHasher isn't guaranteed to be stable across releases, so the
+    //seed is put together by hand
+    fn seed(rule: &Rule, date: NaiveDate) -> u64 {
+        ((date.num_days_from_ce() as u64) << 32) | (rule.serial as u64 & 0xffff_ffff)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn rule(random: Random) -> Rule {
+            Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Tue".to_string()],
+                interval: 30,
+                from: 9,
+                to: 12,
+                random: Some(random),
+                ..Default::default()
+            }
+        }
+
+        fn date(d: u32) -> NaiveDate {
+            NaiveDate::from_ymd_opt(2026, 1, d).unwrap()
+        }
+
+        fn minutes(times: &[(usize, usize)]) -> Vec<usize> {
+            times.iter().map(|(h, m)| h * 60 + m).collect()
+        }
+
+        #[test]
+        fn test_jitter() {
+            let r = rule(Random::Jitter { minutes: 5 });
+            let times = minutes(&random_times(&r, date(5)));
+            let fixed = minutes(&interval_times(&r));
+
+            assert_eq!(times.len(), fixed.len());
+            for (t, f) in times.iter().zip(fixed.iter()) {
+                assert!(t.abs_diff(*f) <= 5, "{} {}", t, f);
+                assert!((9 * 60..=12 * 60).contains(t));
+            }
+
+            //same day, same alarms
+            assert_eq!(random_times(&r, date(5)), random_times(&r, date(5)));
+            assert_ne!(random_times(&r, date(5)), random_times(&r, date(6)));
+            assert!(random_times(&r, date(7)).is_empty());
+        }
+
+        #[test]
+        fn test_breaks() {
+            let r = rule(Random::Breaks {
+                count: 4,
+                min_gap: 40,
+            });
+
+            for d in [5, 6, 12, 13, 19, 20] {
+                let times = minutes(&random_times(&r, date(d)));
+                assert_eq!(times.len(), 4);
+                assert!(times.windows(2).all(|w| w[1] - w[0] >= 40), "{:?}", times);
+                assert!(times.iter().all(|t| (9 * 60..=12 * 60).contains(t)));
+            }
+
+            //the whole window is used up by the gaps
+            let tight = rule(Random::Breaks {
+                count: 4,
+                min_gap: 60,
+            });
+            assert_eq!(
+                random_times(&tight, date(5)),
+                vec![(9, 0), (10, 0), (11, 0), (12, 0)]
+            );
+        }
+
+        #[test]
+        fn test_validate_random() {
+            assert!(validate_random(&rule(Random::Jitter { minutes: 10 })).is_ok());
+            assert!(validate_random(&rule(Random::Breaks {
+                count: 4,
+                min_gap: 60
+            }))
+            .is_ok());
+            assert!(validate_random(&rule(Random::Breaks {
+                count: 5,
+                min_gap: 60
+            }))
+            .is_err());
+            assert!(validate_random(&rule(Random::Breaks {
+                count: 0,
+                min_gap: 0
+            }))
+            .is_err());
+
+            //gaps too large to count must not wrap around to a small number
+            let huge = rule(Random::Breaks {
+                count: 3,
+                min_gap: usize::MAX / 2 + 1,
+            });
+            assert!(validate_random(&huge).is_err());
+            assert!(random_times(&huge, date(5)).is_empty());
+        }
+    }
+}
+
+pub use random::*;
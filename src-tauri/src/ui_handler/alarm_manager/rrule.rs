@@ -0,0 +1,541 @@
+//! Minimal RFC 5545 recurrence rules. Supports the parts commonly found in
+//! meeting invites: FREQ (MINUTELY to MONTHLY), INTERVAL, COUNT, UNTIL,
+//! BYDAY, BYMONTHDAY, BYHOUR and BYMINUTE. Resolution is one minute.
+mod rrule {
+    use chrono::{
+        Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday,
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Frequency {
+        Minutely,
+        Hourly,
+        Daily,
+        Weekly,
+        Monthly,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct RRule {
+        pub freq: Frequency,
+        pub interval: u32,
+        pub count: Option<u32>,
+        pub until: Option<NaiveDateTime>,
+        /// Weekdays with an optional ordinal e.g. 2MO or -1FR
+        pub by_day: Vec<(Option<i32>, Weekday)>,
+        pub by_month_day: Vec<i32>,
+        pub by_hour: Vec<u32>,
+        pub by_minute: Vec<u32>,
+    }
+
+    impl RRule {
+        /// Parses a rule like `FREQ=WEEKLY;BYDAY=MO,WE`. The `RRULE:` prefix is optional
+        pub fn parse(s: &str) -> Result<Self, String> {
+            let s = s.trim();
+            let s = s.strip_prefix("RRULE:").unwrap_or(s);
+
+            let mut freq = None;
+            let mut rule = RRule {
+                freq: Frequency::Daily,
+                interval: 1,
+                count: None,
+                until: None,
+                by_day: Vec::new(),
+                by_month_day: Vec::new(),
+                by_hour: Vec::new(),
+                by_minute: Vec::new(),
+            };
+
+            for part in s.split(';').filter(|p| !p.is_empty()) {
+                let (key, value) = part
+                    .split_once('=')
+                    .ok_or(format!("Invalid rule part {}", part))?;
+
+                match key.to_uppercase().as_str() {
+                    "FREQ" => freq = Some(parse_freq(value)?),
+                    "INTERVAL" => rule.interval = parse_number(key, value, 1, u32::MAX)?,
+                    "COUNT" => rule.count = Some(parse_number(key, value, 1, u32::MAX)?),
+                    "UNTIL" => rule.until = Some(parse_until(value)?),
+                    "BYDAY" => rule.by_day = parse_list(value, parse_by_day)?,
+                    "BYMONTHDAY" => {
+                        rule.by_month_day = parse_list(value, |v| {
+                            let d: i32 = parse_number(key, v, -31, 31)?;
+                            if d == 0 {
+                                return Err("BYMONTHDAY can not be 0".to_string());
+                            }
+                            Ok(d)
+                        })?
+                    }
+                    "BYHOUR" => rule.by_hour = parse_list(value, |v| parse_number(key, v, 0, 23))?,
+                    "BYMINUTE" => {
+                        rule.by_minute = parse_list(value, |v| parse_number(key, v, 0, 59))?
+                    }
+                    "WKST" => (),
+                    _ => return Err(format!("Unsupported rule part {}", key)),
+                }
+            }
+
+            if rule.count.is_some() && rule.until.is_some() {
+                return Err("COUNT and UNTIL can not be used together".to_string());
+            }
+
+            rule.freq = freq.ok_or("FREQ is required".to_string())?;
+            rule.by_hour.sort();
+            rule.by_minute.sort();
+            Ok(rule)
+        }
+
+        /// Occurrences of the rule starting at `dtstart` which fall at or
+        /// after `from` and before `to`. COUNT is counted from `dtstart`
+        pub fn between(
+            &self,
+            dtstart: NaiveDateTime,
+            from: NaiveDateTime,
+            to: NaiveDateTime,
+        ) -> Vec<NaiveDateTime> {
+            let dtstart = dtstart.with_second(0).unwrap().with_nanosecond(0).unwrap();
+            let mut occurrences = Vec::new();
+            let mut seen = 0;
+            //COUNT needs every occurrence since dtstart to be counted
+            let mut k: u32 = match self.count {
+                Some(_) => 0,
+                None => self.periods_before(dtstart, from),
+            };
+
+            while let Some(period) = self.period_start(dtstart, k) {
+                if period >= to || self.until.is_some_and(|u| period > u) {
+                    break;
+                }
+
+                for t in self.expand(dtstart, period) {
+                    if t < dtstart {
+                        continue;
+                    }
+
+                    if self.until.is_some_and(|u| t > u)
+                        || self.count.is_some_and(|c| seen >= c)
+                        || t >= to
+                    {
+                        return occurrences;
+                    }
+
+                    seen += 1;
+                    if t >= from {
+                        occurrences.push(t);
+                    }
+                }
+
+                k = match k.checked_add(self.interval) {
+                    Some(k) => k,
+                    None => break,
+                };
+            }
+
+            occurrences
+        }
+
+        /// Number of whole periods, rounded down to a multiple of INTERVAL,
+        /// which end before `from` and so can be skipped
+        fn periods_before(&self, dtstart: NaiveDateTime, from: NaiveDateTime) -> u32 {
+            let first = match self.period_start(dtstart, 0) {
+                Some(first) if first < from => first,
+                _ => return 0,
+            };
+
+            let periods = match self.freq {
+                Frequency::Minutely => (from - first).num_minutes(),
+                Frequency::Hourly => (from - first).num_hours(),
+                Frequency::Daily => (from - first).num_days(),
+                Frequency::Weekly => (from - first).num_weeks(),
+                Frequency::Monthly => {
+                    (from.year() - first.year()) as i64 * 12 + from.month() as i64
+                        - first.month() as i64
+                }
+            };
+
+            //the period containing `from` is not skipped
+            let periods = u32::try_from(periods.max(0)).unwrap_or(u32::MAX);
+            periods / self.interval * self.interval
+        }
+
+        /// Start of the k-th period after the one containing `dtstart`
+        fn period_start(&self, dtstart: NaiveDateTime, k: u32) -> Option<NaiveDateTime> {
+            let k = k as i64;
+            let midnight = dtstart.date().and_time(NaiveTime::MIN);
+
+            match self.freq {
+                Frequency::Minutely => dtstart
+                    .with_second(0)?
+                    .checked_add_signed(Duration::minutes(k)),
+                Frequency::Hourly => {
+                    let hour = dtstart.with_minute(0)?.with_second(0)?;
+                    hour.checked_add_signed(Duration::hours(k))
+                }
+                Frequency::Daily => midnight.checked_add_signed(Duration::days(k)),
+                Frequency::Weekly => {
+                    let monday =
+                        midnight - Duration::days(dtstart.weekday().num_days_from_monday() as i64);
+                    monday.checked_add_signed(Duration::weeks(k))
+                }
+                Frequency::Monthly => {
+                    let first = midnight.with_day(1)?;
+                    first.checked_add_months(Months::new(k as u32))
+                }
+            }
+        }
+
+        /// Candidate occurrences within the period starting at `period`, sorted
+        fn expand(&self, dtstart: NaiveDateTime, period: NaiveDateTime) -> Vec<NaiveDateTime> {
+            let hours = if self.by_hour.is_empty() {
+                vec![dtstart.hour()]
+            } else {
+                self.by_hour.clone()
+            };
+
+            let minutes = if self.by_minute.is_empty() {
+                vec![dtstart.minute()]
+            } else {
+                self.by_minute.clone()
+            };
+
+            let mut candidates: Vec<NaiveDateTime> = match self.freq {
+                Frequency::Minutely => vec![period],
+                Frequency::Hourly => minutes
+                    .iter()
+                    .filter_map(|m| period.with_minute(*m))
+                    .collect(),
+                _ => {
+                    let dates = self.dates_in_period(dtstart, period.date());
+                    let mut v = Vec::new();
+                    for d in dates {
+                        for h in &hours {
+                            for m in &minutes {
+                                if let Some(t) = NaiveTime::from_hms_opt(*h, *m, 0) {
+                                    v.push(d.and_time(t));
+                                }
+                            }
+                        }
+                    }
+                    v
+                }
+            };
+
+            // BYxxx parts coarser than the frequency limit the candidates
+            candidates.retain(|t| {
+                let by_hour = match self.freq {
+                    Frequency::Minutely | Frequency::Hourly => {
+                        self.by_hour.is_empty() || self.by_hour.contains(&t.hour())
+                    }
+                    _ => true,
+                };
+
+                let by_minute = match self.freq {
+                    Frequency::Minutely => {
+                        self.by_minute.is_empty() || self.by_minute.contains(&t.minute())
+                    }
+                    _ => true,
+                };
+
+                let by_day = match self.freq {
+                    Frequency::Weekly | Frequency::Monthly => true,
+                    _ => {
+                        self.by_day.is_empty()
+                            || self.by_day.iter().any(|(_, wd)| *wd == t.weekday())
+                    }
+                };
+
+                let by_month_day = match self.freq {
+                    Frequency::Monthly => true,
+                    _ => {
+                        self.by_month_day.is_empty()
+                            || matches_month_day(t.date(), &self.by_month_day)
+                    }
+                };
+
+                by_hour && by_minute && by_day && by_month_day
+            });
+
+            candidates.sort();
+            candidates.dedup();
+            candidates
+        }
+
+        /// Dates on which occurrences fall for DAILY and longer frequencies
+        fn dates_in_period(&self, dtstart: NaiveDateTime, start: NaiveDate) -> Vec<NaiveDate> {
+            match self.freq {
+                Frequency::Weekly => {
+                    let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                        vec![dtstart.weekday()]
+                    } else {
+                        self.by_day.iter().map(|(_, wd)| *wd).collect()
+                    };
+
+                    weekdays
+                        .iter()
+                        .map(|wd| start + Duration::days(wd.num_days_from_monday() as i64))
+                        .filter(|d| {
+                            self.by_month_day.is_empty()
+                                || matches_month_day(*d, &self.by_month_day)
+                        })
+                        .collect()
+                }
+                Frequency::Monthly => {
+                    let days = days_in_month(start);
+                    let all: Vec<NaiveDate> = (0..days)
+                        .map(|i| start + Duration::days(i as i64))
+                        .collect();
+
+                    if !self.by_month_day.is_empty() {
+                        all.into_iter()
+                            .filter(|d| matches_month_day(*d, &self.by_month_day))
+                            .filter(|d| {
+                                self.by_day.is_empty()
+                                    || self.by_day.iter().any(|(_, wd)| *wd == d.weekday())
+                            })
+                            .collect()
+                    } else if !self.by_day.is_empty() {
+                        all.into_iter()
+                            .filter(|d| {
+                                self.by_day
+                                    .iter()
+                                    .any(|(n, wd)| matches_nth_weekday(*d, *n, *wd))
+                            })
+                            .collect()
+                    } else {
+                        all.into_iter()
+                            .filter(|d| d.day() == dtstart.day())
+                            .collect()
+                    }
+                }
+                _ => vec![start],
+            }
+        }
+    }
+
+    /// Checks if `date` is the n-th (or n-th from last when negative)
+    /// `weekday` of its month. Any occurrence matches when `n` is None
+    pub fn matches_nth_weekday(date: NaiveDate, n: Option<i32>, weekday: Weekday) -> bool {
+        if date.weekday() != weekday {
+            return false;
+        }
+
+        match n {
+            None => true,
+            Some(n) if n > 0 => (date.day() as i32 - 1) / 7 + 1 == n,
+            Some(n) => {
+                let from_end = days_in_month(date) as i32 - date.day() as i32;
+                -(from_end / 7 + 1) == n
+            }
+        }
+    }
+
+    fn matches_month_day(date: NaiveDate, days: &[i32]) -> bool {
+        let last = days_in_month(date) as i32;
+        days.iter().any(|d| {
+            let d = if *d < 0 { last + d + 1 } else { *d };
+            d == date.day() as i32
+        })
+    }
+
+    fn days_in_month(date: NaiveDate) -> u32 {
+        let first = date.with_day(1).unwrap();
+        let next = first + Months::new(1);
+        (next - first).num_days() as u32
+    }
+
+    fn parse_freq(value: &str) -> Result<Frequency, String> {
+        match value.to_uppercase().as_str() {
+            "MINUTELY" => Ok(Frequency::Minutely),
+            "HOURLY" => Ok(Frequency::Hourly),
+            "DAILY" => Ok(Frequency::Daily),
+            "WEEKLY" => Ok(Frequency::Weekly),
+            "MONTHLY" => Ok(Frequency::Monthly),
+            _ => Err(format!("Unsupported FREQ {}", value)),
+        }
+    }
+
+    fn parse_number<T>(key: &str, value: &str, min: T, max: T) -> Result<T, String>
+    where
+        T: std::str::FromStr + PartialOrd + std::fmt::Display,
+    {
+        match value.trim().parse::<T>() {
+            Ok(n) if n >= min && n <= max => Ok(n),
+            _ => Err(format!("Invalid {} value {}", key, value)),
+        }
+    }
+
+    fn parse_list<T, F>(value: &str, f: F) -> Result<Vec<T>, String>
+    where
+        F: Fn(&str) -> Result<T, String>,
+    {
+        value.split(',').map(|v| f(v.trim())).collect()
+    }
+
+    fn parse_by_day(value: &str) -> Result<(Option<i32>, Weekday), String> {
+        //the day is the last two characters, which may not be single bytes
+        let split = match value.char_indices().rev().nth(1) {
+            Some((i, _)) => i,
+            None => return Err(format!("Invalid BYDAY value {}", value)),
+        };
+
+        let (n, day) = value.split_at(split);
+        let weekday = ical_weekday(day).ok_or(format!("Invalid BYDAY value {}", value))?;
+
+        if n.is_empty() {
+            return Ok((None, weekday));
+        }
+
+        match n.parse::<i32>() {
+            Ok(n) if n != 0 && (-5..=5).contains(&n) => Ok((Some(n), weekday)),
+            _ => Err(format!("Invalid BYDAY value {}", value)),
+        }
+    }
+
+    fn parse_until(value: &str) -> Result<NaiveDateTime, String> {
+        let value = value.trim_end_matches('Z');
+        if let Ok(t) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+            return Ok(t);
+        }
+
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|d| d.and_hms_opt(23, 59, 59).unwrap())
+            .map_err(|_| format!("Invalid UNTIL value {}", value))
+    }
+
+    /// Two letter iCalendar weekday e.g. MO
+    pub fn ical_weekday(day: &str) -> Option<Weekday> {
+        match day.to_uppercase().as_str() {
+            "MO" => Some(Weekday::Mon),
+            "TU" => Some(Weekday::Tue),
+            "WE" => Some(Weekday::Wed),
+            "TH" => Some(Weekday::Thu),
+            "FR" => Some(Weekday::Fri),
+            "SA" => Some(Weekday::Sat),
+            "SU" => Some(Weekday::Sun),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, min, 0)
+                .unwrap()
+        }
+
+        #[test]
+        fn test_weekly_byday() {
+            let rule = RRule::parse("RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=3").unwrap();
+            let occurrences = rule.between(
+                at(2026, 1, 5, 10, 0),
+                at(2026, 1, 1, 0, 0),
+                at(2026, 2, 1, 0, 0),
+            );
+
+            assert_eq!(
+                occurrences,
+                vec![
+                    at(2026, 1, 5, 10, 0),
+                    at(2026, 1, 7, 10, 0),
+                    at(2026, 1, 12, 10, 0)
+                ]
+            );
+        }
+
+        #[test]
+        fn test_window_and_until() {
+            let rule = RRule::parse("FREQ=DAILY;INTERVAL=2;UNTIL=20260112T235959Z").unwrap();
+            let occurrences = rule.between(
+                at(2026, 1, 1, 9, 0),
+                at(2026, 1, 6, 0, 0),
+                at(2026, 2, 1, 0, 0),
+            );
+
+            assert_eq!(
+                occurrences,
+                vec![
+                    at(2026, 1, 7, 9, 0),
+                    at(2026, 1, 9, 9, 0),
+                    at(2026, 1, 11, 9, 0)
+                ]
+            );
+        }
+
+        #[test]
+        fn test_monthly_nth_weekday() {
+            let rule = RRule::parse("FREQ=MONTHLY;BYDAY=-1FR;BYHOUR=16;BYMINUTE=30").unwrap();
+            let occurrences = rule.between(
+                at(2026, 1, 1, 0, 0),
+                at(2026, 1, 1, 0, 0),
+                at(2026, 4, 1, 0, 0),
+            );
+
+            assert_eq!(
+                occurrences,
+                vec![
+                    at(2026, 1, 30, 16, 30),
+                    at(2026, 2, 27, 16, 30),
+                    at(2026, 3, 27, 16, 30)
+                ]
+            );
+        }
+
+        #[test]
+        fn test_minutely() {
+            let rule = RRule::parse("FREQ=MINUTELY;INTERVAL=90;BYHOUR=9,10,11,12").unwrap();
+            let occurrences = rule.between(
+                at(2026, 1, 5, 9, 15),
+                at(2026, 1, 5, 0, 0),
+                at(2026, 1, 6, 0, 0),
+            );
+
+            assert_eq!(
+                occurrences,
+                vec![
+                    at(2026, 1, 5, 9, 15),
+                    at(2026, 1, 5, 10, 45),
+                    at(2026, 1, 5, 12, 15)
+                ]
+            );
+        }
+
+        #[test]
+        fn test_old_dtstart() {
+            let dtstart = at(2020, 1, 1, 9, 15);
+            let from = at(2026, 1, 5, 9, 0);
+            let to = at(2026, 1, 5, 13, 0);
+
+            let rule = RRule::parse("FREQ=MINUTELY;INTERVAL=90").unwrap();
+            let expected: Vec<NaiveDateTime> = (0..240)
+                .map(|m| from + Duration::minutes(m))
+                .filter(|t| (*t - dtstart).num_minutes() % 90 == 0)
+                .collect();
+            assert_eq!(rule.between(dtstart, from, to), expected);
+            assert_eq!(expected.len(), 3);
+
+            let rule = RRule::parse("FREQ=MONTHLY;INTERVAL=6;BYMONTHDAY=5").unwrap();
+            assert_eq!(
+                rule.between(dtstart, from, to + Duration::days(1)),
+                vec![at(2026, 1, 5, 9, 15)]
+            );
+        }
+
+        #[test]
+        fn test_invalid() {
+            assert!(RRule::parse("BYDAY=MO").is_err());
+            assert!(RRule::parse("FREQ=YEARLY").is_err());
+            assert!(RRule::parse("FREQ=DAILY;BYHOUR=24").is_err());
+            assert!(RRule::parse("FREQ=WEEKLY;BYDAY=XX").is_err());
+            assert!(RRule::parse("FREQ=WEEKLY;BYDAY=€").is_err());
+            assert!(RRule::parse("FREQ=WEEKLY;BYDAY=1€MO").is_err());
+            assert!(RRule::parse("FREQ=WEEKLY;BYDAY=MOé").is_err());
+            assert!(RRule::parse("FREQ=DAILY;COUNT=2;UNTIL=20260101").is_err());
+        }
+    }
+}
+
+pub use rrule::*;
@@ -0,0 +1,320 @@
+//! Dry-run of a rule set over an arbitrary date range. Walks the range
+//! minute by minute, the same way AlarmManager does, so DST gaps and
+//! repeated hours show up exactly as they would at runtime.
+mod simulator {
+    use super::super::alarm_utils::*;
+    use crate::ui_handler::Alarm;
+
+    use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Timelike, Utc};
+    use serde::ser::SerializeStruct;
+    use serde::{Deserialize, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    /// A span of time during which alarms are not played e.g. screen locked.
+    /// Given by the UI as RFC 3339 times
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct Pause {
+        pub from: DateTime<Utc>,
+        pub to: DateTime<Utc>,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Outcome {
+        Played,
+        Paused,
+        /// Dropped for being within the minimum gap of another alarm
+        Merged(Merge),
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct SimulatedEvent {
+        pub at: DateTime<FixedOffset>,
+        pub alarm: Alarm,
+        pub outcome: Outcome,
+    }
+
+    impl Serialize for SimulatedEvent {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let (outcome, merge) = match &self.outcome {
+                Outcome::Played => ("played", None),
+                Outcome::Paused => ("paused", None),
+                Outcome::Merged(m) => ("merged", Some(m)),
+            };
+
+            let mut state = serializer.serialize_struct("SimulatedEvent", 4)?;
+            state.serialize_field("at", &self.at.to_rfc3339())?;
+            state.serialize_field("alarm", &self.alarm)?;
+            state.serialize_field("outcome", outcome)?;
+            state.serialize_field("merge", &merge)?;
+            state.end()
+        }
+    }
+
+    /// Returns the alarms the given schedule would produce between `from`
+    /// (inclusive) and `to` (exclusive), in wall-clock time of `from`'s timezone.
+    /// Alarms dropped for the minimum gap are included as merged
+    pub fn simulate<Tz: TimeZone>(
+        schedule: &Schedule,
+        from: DateTime<Tz>,
+        to: DateTime<Tz>,
+        pauses: &[Pause],
+    ) -> Vec<SimulatedEvent> {
+        let tz = from.timezone();
+        let end = to.with_timezone(&Utc);
+
+        let mut t = from.with_timezone(&Utc);
+        let truncated = t.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap();
+        if truncated < t {
+            t = truncated + Duration::minutes(1);
+        } else {
+            t = truncated;
+        }
+
+        //alarms of the date being walked, as cron and RRULE rules are costly to expand
+        let mut walked: Option<NaiveDate> = None;
+        let mut alarms: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut merges: Vec<Merge> = Vec::new();
+
+        let mut events = Vec::new();
+        while t < end {
+            let local = t.with_timezone(&tz);
+            let date = local.date_naive();
+            let day = local.weekday();
+            let hour = local.hour() as usize;
+            let min = local.minute() as usize;
+
+            if walked != Some(date) {
+                (alarms, merges) = schedule.preview_on(date);
+                walked = Some(date);
+            }
+
+            for m in merges.iter().filter(|m| m.hour == hour && m.min == min) {
+                events.push(SimulatedEvent {
+                    at: local.fixed_offset(),
+                    alarm: Alarm {
+                        day,
+                        date,
+                        hour,
+                        min,
+                        category: schedule.category().clone(),
+                    },
+                    outcome: Outcome::Merged(m.clone()),
+                });
+            }
+
+            if alarms.get(&hour).is_some_and(|mins| mins.contains(&min)) {
+                let outcome = if pauses.iter().any(|p| p.from <= t && t < p.to) {
+                    Outcome::Paused
+                } else {
+                    Outcome::Played
+                };
+
+                events.push(SimulatedEvent {
+                    at: local.fixed_offset(),
+                    alarm: Alarm {
+                        day,
+                        date,
+                        hour,
+                        min,
+                        category: schedule.category().clone(),
+                    },
+                    outcome,
+                });
+            }
+
+            t += Duration::minutes(1);
+        }
+
+        events
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::ui_handler::{Category, Rule, RuleKind, Settings};
+        use chrono::Weekday;
+        use chrono_tz::Europe::Berlin;
+
+        fn schedule(rules: Vec<Rule>) -> Schedule {
+            Schedule::for_category(
+                &Settings {
+                    rules,
+                    ..Default::default()
+                },
+                &Category::Break,
+            )
+        }
+
+        fn sunday_rule() -> Rule {
+            Rule {
+                serial: 1,
+                days: vec!["Sun".to_string()],
+                interval: 30,
+                from: 1,
+                to: 3,
+                ..Default::default()
+            }
+        }
+
+        fn berlin(y: i32, m: u32, d: u32) -> DateTime<chrono_tz::Tz> {
+            let naive = NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap();
+            Berlin.from_local_datetime(&naive).unwrap()
+        }
+
+        fn times(events: &[SimulatedEvent]) -> Vec<(u32, u32)> {
+            events
+                .iter()
+                .map(|e| (e.at.hour(), e.at.minute()))
+                .collect()
+        }
+
+        #[test]
+        fn test_simulate_week() {
+            let rule = Rule {
+                serial: 1,
+                days: vec!["Mon".to_string(), "Fri".to_string()],
+                interval: 30,
+                from: 9,
+                to: 11,
+                ..Default::default()
+            };
+
+            let from = berlin(2026, 1, 5);
+            let to = from + Duration::days(7);
+            let events = simulate(&schedule(vec![rule]), from, to, &[]);
+
+            assert_eq!(events.len(), 8);
+            assert_eq!(events[0].alarm.day, Weekday::Mon);
+            assert_eq!(events[0].at.to_rfc3339(), "2026-01-05T09:30:00+01:00");
+            assert_eq!(events[7].alarm.day, Weekday::Fri);
+            assert_eq!(events[7].at.to_rfc3339(), "2026-01-09T11:00:00+01:00");
+            assert!(events.iter().all(|e| e.outcome == Outcome::Played));
+        }
+
+        #[test]
+        fn test_simulate_dst_spring_forward() {
+            // 02:00 -> 03:00 on 29 Mar 2026. 2:00 and 2:30 never happen
+            let from = berlin(2026, 3, 29);
+            let to = berlin(2026, 3, 30);
+            let events = simulate(&schedule(vec![sunday_rule()]), from, to, &[]);
+
+            assert_eq!(times(&events), vec![(1, 30), (3, 0)]);
+        }
+
+        #[test]
+        fn test_simulate_dst_fall_back() {
+            // 03:00 -> 02:00 on 25 Oct 2026. 2:00 and 2:30 happen twice
+            let from = berlin(2026, 10, 25);
+            let to = berlin(2026, 10, 26);
+            let events = simulate(&schedule(vec![sunday_rule()]), from, to, &[]);
+
+            assert_eq!(
+                times(&events),
+                vec![(1, 30), (2, 0), (2, 30), (2, 0), (2, 30), (3, 0)]
+            );
+            assert_eq!(events[1].at.offset().local_minus_utc(), 2 * 3600);
+            assert_eq!(events[3].at.offset().local_minus_utc(), 3600);
+        }
+
+        #[test]
+        fn test_simulate_pause() {
+            let from = berlin(2026, 1, 4);
+            let to = berlin(2026, 1, 5);
+            let pause = Pause {
+                from: (from + Duration::hours(2)).with_timezone(&Utc),
+                to: (from + Duration::hours(3)).with_timezone(&Utc),
+            };
+            let parsed: Pause = serde_json::from_value(serde_json::json!({
+                "from": "2026-01-04T02:00:00+01:00",
+                "to": "2026-01-04T03:00:00+01:00"
+            }))
+            .unwrap();
+            assert_eq!(parsed, pause);
+            let events = simulate(&schedule(vec![sunday_rule()]), from, to, &[pause]);
+
+            let outcomes: Vec<Outcome> = events.iter().map(|e| e.outcome.clone()).collect();
+            assert_eq!(
+                outcomes,
+                vec![
+                    Outcome::Played,
+                    Outcome::Paused,
+                    Outcome::Paused,
+                    Outcome::Played
+                ]
+            );
+        }
+
+        #[test]
+        fn test_simulate_merges() {
+            let cron = |serial, expression: &str, priority| Rule {
+                serial,
+                days: vec!["Mon".to_string()],
+                kind: RuleKind::Cron,
+                expression: Some(expression.to_string()),
+                priority,
+                ..Default::default()
+            };
+
+            let settings = Settings {
+                rules: vec![
+                    Rule {
+                        serial: 1,
+                        days: vec!["Mon".to_string()],
+                        interval: 30,
+                        from: 9,
+                        to: 10,
+                        priority: 1,
+                        ..Default::default()
+                    },
+                    cron(2, "32 9 * * *", 0),
+                    cron(3, "28 9 * * *", 2),
+                ],
+                min_gap: 5,
+                ..Default::default()
+            };
+
+            let from = berlin(2026, 1, 5);
+            let events = simulate(
+                &Schedule::for_category(&settings, &Category::Break),
+                from,
+                from + Duration::days(1),
+                &[],
+            );
+
+            let merge = |hour, min, serial, into_min, into_serial| {
+                Outcome::Merged(Merge {
+                    hour,
+                    min,
+                    serial,
+                    into_hour: 9,
+                    into_min,
+                    into_serial,
+                })
+            };
+
+            let outcomes: Vec<Outcome> = events.iter().map(|e| e.outcome.clone()).collect();
+            assert_eq!(
+                outcomes,
+                vec![
+                    Outcome::Played,
+                    merge(9, 30, 1, 28, 3),
+                    merge(9, 32, 2, 28, 3),
+                    Outcome::Played,
+                ]
+            );
+            assert_eq!(times(&events), vec![(9, 28), (9, 30), (9, 32), (10, 0)]);
+
+            let json = serde_json::to_value(&events[1]).unwrap();
+            assert_eq!(json["outcome"], "merged");
+            assert_eq!(json["merge"]["into-serial"], 3);
+        }
+    }
+}
+
+pub use simulator::*;
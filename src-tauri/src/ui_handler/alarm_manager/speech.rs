@@ -0,0 +1,73 @@
+//! Messages of spoken reminders. The message set for a category may hold
+//! placeholders, filled in when the alarm plays:
+//! {rule} the serial of the rule the alarm comes from, {text} the reminder
+//! text of the category, {activity} the title of the activity picked and
+//! {next} the time of the category's next alarm. Placeholders with nothing
+//! to fill in, e.g. {rule} for timers, are left empty
+mod speech {
+    use crate::ui_handler::{Activity, Alarm};
+
+    pub fn fill(
+        message: &str,
+        rule: Option<usize>,
+        text: &str,
+        activity: Option<&Activity>,
+        next: Option<&Alarm>,
+    ) -> String {
+        let activity = activity.map(|a| a.title.as_str()).unwrap_or_default();
+        let next = next
+            .map(|a| format!("{:02}:{:02}", a.hour, a.min))
+            .unwrap_or_default();
+
+        let rule = rule.map(|r| r.to_string()).unwrap_or_default();
+
+        message
+            .replace("{rule}", &rule)
+            .replace("{text}", text)
+            .replace("{activity}", activity)
+            .replace("{next}", &next)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::ui_handler::Category;
+        use chrono::{Datelike, NaiveDate};
+
+        #[test]
+        fn test_fill() {
+            let date = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+            let next = Alarm {
+                day: date.weekday(),
+                date,
+                hour: 15,
+                min: 5,
+                category: Category::Break,
+            };
+            let activity = Activity {
+                title: "Neck rolls".to_string(),
+                text: "Roll your head slowly".to_string(),
+                duration: 60,
+                image: None,
+                category: Category::Break,
+            };
+
+            assert_eq!(
+                fill(
+                    "{text}, next break at {next}. Try {activity}. Rule {rule}",
+                    Some(3),
+                    "Time to stand up",
+                    Some(&activity),
+                    Some(&next)
+                ),
+                "Time to stand up, next break at 15:05. Try Neck rolls. Rule 3"
+            );
+            assert_eq!(
+                fill("{text}. {activity}{rule}", None, "Drink", None, None),
+                "Drink. "
+            );
+        }
+    }
+}
+
+pub use speech::*;
@@ -0,0 +1,222 @@
+//! Counts of the reminders played per day and category, kept for the last
+//! STATS_DAYS days, along with a history of the individual reminders for
+//! the last HISTORY_DAYS days and the minutes of active computer use per
+//! day. Saved across restarts
+mod stats {
+    use crate::ui_handler::Category;
+
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    pub const STATS_DAYS: i64 = 90;
+    pub const HISTORY_DAYS: i64 = 7;
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct Counters {
+        pub played: usize,
+        //breaks skipped or left unanswered
+        pub skipped: usize,
+        //breaks held back by a focus session
+        pub deferred: usize,
+    }
+
+    /// A reminder which was played
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Entry {
+        pub at: NaiveDateTime,
+        pub category: Category,
+        #[serde(default)]
+        pub skipped: bool,
+        #[serde(default)]
+        pub deferred: bool,
+        //seconds until the break was acknowledged or skipped
+        #[serde(default)]
+        pub response: Option<i64>,
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct Stats {
+        pub days: BTreeMap<NaiveDate, BTreeMap<Category, Counters>>,
+        pub history: Vec<Entry>,
+        //minutes the computer was in use
+        pub active: BTreeMap<NaiveDate, usize>,
+    }
+
+    impl Stats {
+        /// Counts a reminder, dropping days older than STATS_DAYS and
+        /// entries older than HISTORY_DAYS
+        pub fn record(&mut self, at: NaiveDateTime, category: &Category) {
+            self.prune(at.date());
+            self.counters(at.date(), category).played += 1;
+            self.history.push(Entry {
+                at,
+                category: category.clone(),
+                skipped: false,
+                deferred: false,
+                response: None,
+            });
+        }
+
+        /// Counts a reminder which was due during a focus session but not
+        /// played
+        pub fn defer(&mut self, at: NaiveDateTime, category: &Category) {
+            self.prune(at.date());
+            self.counters(at.date(), category).deferred += 1;
+            self.history.push(Entry {
+                at,
+                category: category.clone(),
+                skipped: false,
+                deferred: true,
+                response: None,
+            });
+        }
+
+        /// Marks a reminder recorded earlier as skipped
+        pub fn skip(&mut self, at: NaiveDateTime, category: &Category) {
+            self.counters(at.date(), category).skipped += 1;
+
+            if let Some(entry) = self.entry(at, category) {
+                entry.skipped = true;
+            }
+        }
+
+        fn entry(&mut self, at: NaiveDateTime, category: &Category) -> Option<&mut Entry> {
+            self.history
+                .iter_mut()
+                .rev()
+                .find(|e| e.at == at && e.category == *category && !e.deferred)
+        }
+
+        /// Records how long a reminder took to be answered. Skipping counts
+        /// as a skipped reminder
+        pub fn respond(
+            &mut self,
+            at: NaiveDateTime,
+            category: &Category,
+            seconds: i64,
+            skipped: bool,
+        ) {
+            if skipped {
+                self.skip(at, category);
+            }
+
+            if let Some(entry) = self.entry(at, category) {
+                entry.response = Some(seconds);
+            }
+        }
+
+        /// Counts a minute of use of the computer. Returns the minutes so
+        /// far on the date
+        pub fn add_active(&mut self, date: NaiveDate) -> usize {
+            self.prune(date);
+            let minutes = self.active.entry(date).or_default();
+            *minutes += 1;
+            *minutes
+        }
+
+        /// Minutes of use on each of the last `days` days up to `today`
+        pub fn active_days(&self, today: NaiveDate, days: usize) -> BTreeMap<NaiveDate, usize> {
+            (0..days as i64)
+                .map(|n| today - Duration::days(n))
+                .map(|d| (d, self.active.get(&d).copied().unwrap_or_default()))
+                .collect()
+        }
+
+        fn prune(&mut self, date: NaiveDate) {
+            let oldest = date - Duration::days(STATS_DAYS - 1);
+            self.days.retain(|d, _| *d >= oldest);
+            self.active.retain(|d, _| *d >= oldest);
+
+            let oldest = date - Duration::days(HISTORY_DAYS - 1);
+            self.history.retain(|e| e.at.date() >= oldest);
+        }
+
+        fn counters(&mut self, date: NaiveDate, category: &Category) -> &mut Counters {
+            self.days
+                .entry(date)
+                .or_default()
+                .entry(category.clone())
+                .or_default()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 1, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        }
+
+        #[test]
+        fn test_stats() {
+            let mut stats = Stats::default();
+            stats.record(at(5, 10, 0), &Category::Break);
+            stats.record(at(5, 10, 20), &Category::Eyes);
+            stats.record(at(5, 10, 40), &Category::Eyes);
+            stats.skip(at(5, 10, 0), &Category::Break);
+            stats.defer(at(5, 11, 0), &Category::Break);
+
+            let saved = serde_json::to_value(&stats).unwrap();
+            assert_eq!(
+                saved["days"]["2026-01-05"],
+                serde_json::json!({
+                    "break": {"played": 1, "skipped": 1, "deferred": 1},
+                    "eyes": {"played": 2, "skipped": 0, "deferred": 0}
+                })
+            );
+            assert_eq!(
+                saved["history"][0],
+                serde_json::json!({
+                    "at": "2026-01-05T10:00:00", "category": "break", "skipped": true,
+                    "deferred": false, "response": null
+                })
+            );
+            assert_eq!(saved["history"][3]["deferred"], true);
+            assert_eq!(serde_json::from_value::<Stats>(saved).unwrap(), stats);
+
+            stats.respond(at(5, 10, 20), &Category::Eyes, 12, false);
+            assert_eq!(stats.history[1].response, Some(12));
+            assert!(!stats.history[1].skipped);
+            stats.respond(at(5, 10, 40), &Category::Eyes, 30, true);
+            assert!(stats.history[2].skipped);
+            assert_eq!(stats.days[&at(5, 0, 0).date()][&Category::Eyes].skipped, 1);
+
+            //old history goes first, then old counts
+            stats.record(at(5, 0, 0) + Duration::days(HISTORY_DAYS), &Category::Water);
+            assert_eq!(stats.history.len(), 1);
+            assert_eq!(stats.days.len(), 2);
+
+            stats.record(at(5, 0, 0) + Duration::days(STATS_DAYS), &Category::Water);
+            assert!(!stats.days.contains_key(&at(5, 0, 0).date()));
+        }
+
+        #[test]
+        fn test_active() {
+            let mut stats = Stats::default();
+            let date = at(5, 0, 0).date();
+            for _ in 0..90 {
+                stats.add_active(date);
+            }
+            assert_eq!(stats.add_active(date + Duration::days(2)), 1);
+
+            let week = stats.active_days(date + Duration::days(2), 7);
+            assert_eq!(week.len(), 7);
+            assert_eq!(week[&date], 90);
+            assert_eq!(week[&(date + Duration::days(1))], 0);
+            assert_eq!(week.keys().next(), Some(&(date - Duration::days(4))));
+
+            stats.add_active(date + Duration::days(STATS_DAYS));
+            assert!(!stats.active.contains_key(&date));
+        }
+    }
+}
+
+pub use stats::*;
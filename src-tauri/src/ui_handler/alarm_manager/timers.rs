@@ -0,0 +1,205 @@
+//! One-shot timers e.g. "break in 25 minutes" or "at 15:40 today". They are
+//! kept apart from the rules, which are matched against the clock every
+//! day, and are saved until they fire
+mod timers {
+    use crate::ui_handler::{Alarm, Category};
+
+    use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike};
+    use log::debug;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    //timers missed by longer e.g. while the app was closed are dropped
+    const STALE_MINUTES: i64 = 60;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Timer {
+        pub id: usize,
+        pub at: NaiveDateTime,
+        #[serde(default)]
+        pub category: Category,
+    }
+
+    impl Timer {
+        pub fn alarm(&self) -> Alarm {
+            Alarm {
+                day: self.at.weekday(),
+                date: self.at.date(),
+                hour: self.at.hour() as usize,
+                min: self.at.minute() as usize,
+                category: self.category.clone(),
+            }
+        }
+    }
+
+    /// Pending timers, earliest first
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct Timers {
+        next_id: usize,
+        timers: Vec<Timer>,
+    }
+
+    impl Timers {
+        /// Adds a timer going off at `at`, rounded up to the minute so that
+        /// it never goes off early
+        pub fn add(&mut self, at: NaiveDateTime, category: &Category) -> Timer {
+            let minute = at
+                .with_second(0)
+                .and_then(|t| t.with_nanosecond(0))
+                .unwrap_or(at);
+
+            self.next_id += 1;
+            let timer = Timer {
+                id: self.next_id,
+                at: if minute < at {
+                    minute + Duration::minutes(1)
+                } else {
+                    minute
+                },
+                category: category.clone(),
+            };
+
+            let pos = self.timers.partition_point(|t| t.at <= timer.at);
+            self.timers.insert(pos, timer.clone());
+            timer
+        }
+
+        /// Removes a timer. Returns false if there was none with the id
+        pub fn cancel(&mut self, id: usize) -> bool {
+            let len = self.timers.len();
+            self.timers.retain(|t| t.id != id);
+            self.timers.len() != len
+        }
+
+        /// Removes the timers due by `now` and returns those missed by no
+        /// more than STALE_MINUTES, e.g. while the app was briefly closed
+        pub fn take_due(&mut self, now: NaiveDateTime) -> Vec<Timer> {
+            let due = self.timers.partition_point(|t| t.at <= now);
+            let stale = now - Duration::minutes(STALE_MINUTES);
+
+            self.timers
+                .drain(..due)
+                .filter(|t| {
+                    if t.at < stale {
+                        debug!("timers: dropping stale timer {:?}", t);
+                    }
+                    t.at >= stale
+                })
+                .collect()
+        }
+
+        pub fn next(&self, category: &Category) -> Option<&Timer> {
+            self.timers.iter().find(|t| t.category == *category)
+        }
+
+        pub fn all(&self) -> &[Timer] {
+            &self.timers
+        }
+    }
+
+    /// When a timer asked for by the UI goes off: either in `minutes` from
+    /// now or `at` a time ("15:40") later today
+    pub fn timer_at(json: &Value, now: NaiveDateTime) -> Result<NaiveDateTime, String> {
+        if let Some(minutes) = json.get("minutes") {
+            return match minutes.as_u64() {
+                Some(m) if m > 0 => Ok(now + Duration::minutes(m as i64)),
+                _ => Err("Minutes must be a whole number above 0".to_string()),
+            };
+        }
+
+        let time = match json.get("at").and_then(Value::as_str) {
+            Some(at) => NaiveTime::parse_from_str(at, "%H:%M")
+                .map_err(|_| format!("Invalid time {}, expected HH:MM", at))?,
+            None => return Err("Either minutes or at is needed".to_string()),
+        };
+
+        let at = now.date().and_time(time);
+        if at <= now {
+            return Err(format!("{} has already passed today", time.format("%H:%M")));
+        }
+
+        Ok(at)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::NaiveDate;
+
+        fn at(h: u32, m: u32, s: u32) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 1, 5)
+                .unwrap()
+                .and_hms_opt(h, m, s)
+                .unwrap()
+        }
+
+        #[test]
+        fn test_timers() {
+            let mut timers = Timers::default();
+            let a = timers.add(at(15, 40, 0), &Category::Break);
+            let b = timers.add(at(10, 25, 30), &Category::Water);
+            let c = timers.add(at(11, 0, 0), &Category::Break);
+
+            //rounded up, never early
+            assert_eq!(b.at, at(10, 26, 0));
+            assert_eq!(
+                timers.all().iter().map(|t| t.id).collect::<Vec<_>>(),
+                vec![b.id, c.id, a.id]
+            );
+            assert_eq!(timers.next(&Category::Break), Some(&c));
+            assert_eq!(timers.next(&Category::Eyes), None);
+
+            assert!(timers.cancel(c.id));
+            assert!(!timers.cancel(c.id));
+            assert_eq!(timers.next(&Category::Break), Some(&a));
+
+            //survives a restart, ids are not reused
+            let saved = serde_json::to_string(&timers).unwrap();
+            let mut timers: Timers = serde_json::from_str(&saved).unwrap();
+            assert!(timers.add(at(15, 0, 0), &Category::Eyes).id > a.id);
+
+            assert_eq!(timers.take_due(at(10, 25, 59)), vec![]);
+            assert_eq!(timers.take_due(at(10, 26, 0)), vec![b]);
+            let due = timers.take_due(at(16, 0, 0));
+            assert_eq!(due.len(), 2);
+            assert_eq!(due[1], a);
+            assert!(timers.all().is_empty());
+
+            //missed by too long
+            timers.add(at(8, 0, 0), &Category::Break);
+            assert_eq!(timers.take_due(at(9, 1, 0)), vec![]);
+            assert!(timers.all().is_empty());
+        }
+
+        #[test]
+        fn test_timer_at() {
+            use serde_json::json;
+
+            let now = at(15, 10, 30);
+            assert_eq!(timer_at(&json!({"minutes": 25}), now), Ok(at(15, 35, 30)));
+            assert_eq!(timer_at(&json!({"at": "15:40"}), now), Ok(at(15, 40, 0)));
+
+            assert!(timer_at(&json!({"minutes": 0}), now).is_err());
+            assert!(timer_at(&json!({"minutes": "soon"}), now).is_err());
+            assert!(timer_at(&json!({"at": "15:10"}), now).is_err());
+            assert!(timer_at(&json!({"at": "25:00"}), now).is_err());
+            assert!(timer_at(&json!({}), now).is_err());
+        }
+
+        #[test]
+        fn test_alarm() {
+            let mut timers = Timers::default();
+            let alarm = timers.add(at(15, 40, 0), &Category::Stretch).alarm();
+            assert_eq!(
+                serde_json::to_value(alarm).unwrap(),
+                serde_json::json!({
+                    "day": "Mon", "date": "2026-01-05", "hour": 15, "min": 40, "category": "stretch"
+                })
+            );
+        }
+    }
+}
+
+pub use timers::*;
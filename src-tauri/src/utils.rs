@@ -68,6 +68,26 @@ mod utils {
         }
     }
 
+    /// Seconds since the last keyboard or mouse input. Works only on Mac
+    /// at the moment
+    pub fn idle_seconds() -> Option<u64> {
+        let buf = Command::new("ioreg")
+            .args(&["-c", "IOHIDSystem", "-r", "-d1", "-a"])
+            .output()
+            .ok()
+            .filter(|buf| buf.status.success())?;
+
+        let v = Value::from_reader(Cursor::new(buf.stdout)).ok()?;
+        let nanos = v
+            .as_array()
+            .and_then(|devices| devices.first())
+            .and_then(|device| device.as_dictionary())
+            .and_then(|dict| dict.get("HIDIdleTime"))
+            .and_then(|idle| idle.as_unsigned_integer())?;
+
+        Some(nanos / 1_000_000_000)
+    }
+
     impl fmt::Debug for LockedState {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             match *self {
@@ -130,6 +150,36 @@ mod utils {
         return "".to_string();
     }
 
+    pub fn get_pomodoro_file_name() -> String {
+        get_app_file_name("pomodoro.json")
+    }
+
+    pub fn get_timers_file_name() -> String {
+        get_app_file_name("timers.json")
+    }
+
+    pub fn get_focus_file_name() -> String {
+        get_app_file_name("focus.json")
+    }
+
+    pub fn get_shown_file_name() -> String {
+        get_app_file_name("shown.json")
+    }
+
+    pub fn get_stats_file_name() -> String {
+        get_app_file_name("stats.json")
+    }
+
+    fn get_app_file_name(name: &str) -> String {
+        match get_app_dir() {
+            Some(mut path) => {
+                path.push(name);
+                path.to_string_lossy().to_string()
+            }
+            None => "".to_string(),
+        }
+    }
+
     fn get_app_dir() -> Option<PathBuf> {
         #[cfg(feature = "debug")]
         {